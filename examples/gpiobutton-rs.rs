@@ -0,0 +1,33 @@
+use std::env;
+use std::path::Path;
+use libgpiod::{GpioChip,EdgeDetection,LineBias,LineEdge};
+
+fn main()  -> Result<(), &'static str> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        return Err("Too few arguments. Usage <gpiochip path> [offset]");
+    }
+
+    let gpiodev = &args[1];
+
+    let offset : u32 = args[2].parse().unwrap();
+
+    let gpiochip = GpioChip::new(&Path::new(gpiodev)).unwrap();
+
+    // The button shorts the line to ground so it is pulled up and a press is a falling edge
+    let button = gpiochip.request_button(&offset, EdgeDetection::BothEdges, LineBias::PullUp, "gpiobutton").unwrap();
+
+    println!("GPIO button {} offset {}", gpiodev, offset);
+
+    for event in button {
+        let event = event.unwrap();
+        match event.edge() {
+            LineEdge::Falling => println!("Pressed at {}", event.timestamp()),
+            LineEdge::Rising => println!("Released at {}", event.timestamp()),
+            LineEdge::Unknown(_) => (),
+        }
+    }
+
+    Ok(())
+}
@@ -20,8 +20,9 @@ fn main() {
         for index in (0..gpiochips.len()).rev() {
             let gpiochip = &gpiochips[index];
             println!("{}", gpiochip);
-            for line_index in 0..*gpiochip.num_lines() {
-                let line_info = gpiochip.get_line_info(&line_index).unwrap();
+            let line_numbers : Vec<u32> = (0..*gpiochip.num_lines()).collect();
+            let line_infos = gpiochip.get_line_infos(&line_numbers).unwrap();
+            for (line_index, line_info) in line_numbers.iter().zip(line_infos) {
                 println!("\t Line \t {}: \t {}", line_index, line_info);
             }
         }
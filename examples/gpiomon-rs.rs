@@ -0,0 +1,30 @@
+use std::env;
+use std::path::Path;
+use libgpiod::{GpioChip,EdgeDetection,LineBias};
+
+fn main()  -> Result<(), &'static str> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        return Err("Too few arguments. Usage <gpiochip path> [offset1] [offset2] ... ");
+    }
+
+    let gpiodev = &args[1];
+
+    let offset : Vec<u32> = args.iter().enumerate()
+        .filter(|&(i, _)| i >= 2)
+        .map(|(_,x)| x.parse().unwrap())
+        .collect();
+
+    let gpiochip = GpioChip::new(&Path::new(gpiodev)).unwrap();
+
+    let line = gpiochip.monitor(&offset, EdgeDetection::BothEdges, LineBias::None, "gpiomon").unwrap();
+
+    println!("GPIO mon {} offset {:?}", gpiodev, offset);
+
+    for event in line {
+        println!("{}", event.unwrap());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use std::env;
+use std::path::Path;
+use libgpiod::{GpioChip,OutputMode};
+
+fn main()  -> Result<(), &'static str> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 6 {
+        return Err("Too few arguments. Usage <gpiochip path> [data offset] [clock offset] [latch offset] [byte]");
+    }
+
+    let gpiodev = &args[1];
+
+    let offset : Vec<u32> = args[2..5].iter()
+        .map(|x| x.parse().unwrap())
+        .collect();
+
+    let byte : u8 = args[5].parse().unwrap();
+
+    let gpiochip = GpioChip::new(&Path::new(gpiodev)).unwrap();
+
+    let line = gpiochip.request_line_values_output(&offset, OutputMode::None, false, "gpioshift").unwrap();
+
+    println!("GPIO shift {} offset {:?}. Byte {:#04x} {:?}", gpiodev, offset, byte, line.shift_out(offset[0], offset[1], offset[2], byte, true));
+
+    Ok(())
+}
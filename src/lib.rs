@@ -7,34 +7,343 @@
 //! 
 //! Since all functionality is dependent on Linux function calls, this crate only compiles for Linux systems.
 //! 
+//! The */dev* and */sys* directories where the GPIO chips are looked up can be changed with the
+//! *GPIOD_DEV_ROOT* and *GPIOD_SYSFS_ROOT* environment variables, e.g. for containers with sysfs mounted
+//! at a different place or for testing against a fixture directory.
+//! 
+//! The GpioChip and GpioLineValue types are both *Send* and *Sync* and their value methods take *&self*,
+//! so a GpioLineValue can be shared between threads (e.g. inside an *Arc*) to read the values from one
+//! thread while another thread sets them. *get_line_value* and *set_line_value* are a single ioctl,
+//! which the kernel serializes. The methods which change only some of the lines (*set_single_line_value*,
+//! *set_subset_line_values*, *set_bits_masked*, *swap_line_values* and *shift_out*) are atomic towards
+//! the other methods of the same request, as they build on the values last written through it under a
+//! lock, but not towards other processes. The timed methods (*pulse*, *pwm*, *blink* and their async
+//! versions) are made of several writes with waits in between, so other writes of the same request can
+//! happen between their steps.
+//! 
 
 #[macro_use]
 extern crate nix;
 
+use std::collections::HashMap;
+use std::env;
 use std::fmt;
 use std::io;
 use std::io::{Error, ErrorKind};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::fs::OpenOptions;
 use std::fs::symlink_metadata;
+use std::ops::ControlFlow;
 use std::os::unix::fs::{MetadataExt, FileTypeExt};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::os::unix::prelude::*;
 use std::os::unix::io::FromRawFd;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use bitflags::bitflags;
 
 fn convert_nix_to_io_result(result: nix::Result<i32>) -> io::Result<i32>{
 	match result {
 		Err(e) => {	
 			match e {
-				nix::Error::Sys(errno) => return Err(io::Error::from(errno)),
-				nix::Error::InvalidPath => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
-				nix::Error::InvalidUtf8 => return Err(io::Error::from(io::ErrorKind::InvalidData)),
-				nix::Error::UnsupportedOperation => return Err(io::Error::from(io::ErrorKind::Other))
+				nix::Error::Sys(errno) => Err(io::Error::from(errno)),
+				nix::Error::InvalidPath => Err(io::Error::from(io::ErrorKind::InvalidInput)),
+				nix::Error::InvalidUtf8 => Err(io::Error::from(io::ErrorKind::InvalidData)),
+				nix::Error::UnsupportedOperation => Err(io::Error::from(io::ErrorKind::Other))
 			}
 		},
-		Ok(v) => return Ok(v),
+		Ok(v) => Ok(v),
+	}
+}
+
+// Run a system call again while it fails with EINTR, in the same way as the blocking calls of std, so
+// that signals received by the process don't surface as errors.
+fn retry_on_interrupt<F: FnMut() -> nix::Result<i32>>(mut call: F) -> io::Result<i32> {
+	loop {
+		match convert_nix_to_io_result(call()) {
+			Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+			result => return result,
+		}
+	}
+}
+
+/// Represents the validation errors of line requests detected by this crate before calling the kernel.
+/// They are returned as the inner error of an *io::Error* of kind *InvalidInput* so they can be told
+/// apart using *io::Error::get_ref* and *downcast_ref*.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpioError {
+	/// The request doesn't name any line.
+	NoLines,
+	/// The line offset is not lower than the number of lines of the chip.
+	InvalidOffset { offset: u32, num_lines: u32 },
+	/// More lines than the kernel accepts in a single request.
+	TooManyLines { requested: usize, max: usize },
+	/// The consumer label doesn't fit in the buffer of the kernel.
+	LabelTooLong { len: usize, max: usize },
+	/// The line at the offset doesn't have the expected name, e.g. because the program runs on another board.
+	LineNameMismatch { offset: u32, expected: String, found: String },
+}
+
+impl fmt::Display for GpioError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			GpioError::NoLines =>
+				write!(f, "No lines requested, a request must hold at least one line"),
+			GpioError::InvalidOffset { offset, num_lines } =>
+				write!(f, "Offset {} exceeds the {} lines of the chip", offset, num_lines),
+			GpioError::TooManyLines { requested, max } =>
+				write!(f, "Requested {} lines but the maximum is {} lines, use a line bank instead", requested, max),
+			GpioError::LabelTooLong { len, max } =>
+				write!(f, "Consumer label is {} bytes long but the maximum is {} bytes", len, max),
+			GpioError::LineNameMismatch { offset, expected, found } =>
+				write!(f, "Line {} is named \"{}\" but \"{}\" was expected", offset, found, expected),
+		}
+	}
+}
+
+impl std::error::Error for GpioError {}
+
+impl From<GpioError> for io::Error {
+	fn from(error: GpioError) -> io::Error {
+		io::Error::new(ErrorKind::InvalidInput, error)
+	}
+}
+
+/// Represents an error of the kernel or of the file system together with the stage of the operation
+/// which failed, e.g. the chip device and the lines of a failed request. It is returned as the inner error
+/// of an *io::Error* of the same kind, so the original error, including its *errno*, stays reachable
+/// through *io::Error::get_ref*, *downcast_ref* and *source* or more simply with *raw_os_error*.
+#[derive(Debug)]
+pub struct ContextError {
+	context: String,
+	error: io::Error,
+}
+
+impl ContextError {
+	/// Get the description of the stage which failed.
+	pub fn context(&self) -> &str {
+		&self.context
+	}
+
+	/// Get the original error.
+	pub fn error(&self) -> &io::Error {
+		&self.error
+	}
+
+	/// Get the *errno* of an error returned by this crate, looking through the stages added to it. Unlike
+	/// *io::Error::raw_os_error*, this also finds the *errno* of errors with context.
+	pub fn raw_os_error(error: &io::Error) -> Option<i32> {
+		error.raw_os_error().or_else(|| error.get_ref()
+			.and_then(|inner| inner.downcast_ref::<ContextError>())
+			.and_then(|context_error| ContextError::raw_os_error(&context_error.error)))
+	}
+}
+
+impl fmt::Display for ContextError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.context, self.error)
+	}
+}
+
+impl std::error::Error for ContextError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.error)
+	}
+}
+
+// Add the stage of an operation which failed to an error, keeping its kind so callers can still match on
+// it, e.g. to tell a missing device node from a failed sysfs cross-check, and the original error as source.
+fn error_context(stage: &str) -> impl FnOnce(io::Error) -> io::Error + '_ {
+	move |e| Error::new(e.kind(), ContextError { context: stage.to_string(), error: e })
+}
+
+// Directory where the GPIO chip devices are found, /dev unless overridden by GPIOD_DEV_ROOT
+fn dev_root() -> PathBuf {
+	env::var_os("GPIOD_DEV_ROOT").map_or_else(|| PathBuf::from("/dev"), PathBuf::from)
+}
+
+// Directory of the GPIO devices in sysfs, /sys/bus/gpio/devices unless sysfs is moved by GPIOD_SYSFS_ROOT
+fn sysfs_gpio_devices() -> PathBuf {
+	env::var_os("GPIOD_SYSFS_ROOT").map_or_else(|| PathBuf::from("/sys"), PathBuf::from).join("bus/gpio/devices")
+}
+
+// Set the values of the lines of a line handle file descriptor
+fn write_handle_values(fd: RawFd, offsets: &[u32], values: &[u8]) -> io::Result<()> {
+	let mut data = gpio_ioctl::GpioHandleData::default();
+
+	data.values[..values.len()].copy_from_slice(values);
+
+	gpio_ioctl_call("gpio_set_line_values", offsets, || unsafe { gpio_ioctl::gpio_set_line_values(fd, &mut data) })?;
+
+	Ok(())
+}
+
+// Read a whole fixed size record, such as an event, from a GPIO file descriptor. The kernel always
+// returns whole records, but a short read is still completed instead of decoding partial data, and the
+// end of file, e.g. after the device was removed, is told apart from a record cut short.
+fn read_record(mut file: &File, buffer: &mut [u8]) -> io::Result<()> {
+	let mut filled = 0;
+
+	while filled < buffer.len() {
+		match file.read(&mut buffer[filled..]) {
+			Ok(0) if filled == 0 => return Err(Error::new(ErrorKind::UnexpectedEof, "GPIO file descriptor reached end of file")),
+			Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof,
+				format!("GPIO record cut short after {} of {} bytes", filled, buffer.len()))),
+			Ok(read) => filled += read,
+			Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		}
+	}
+
+	Ok(())
+}
+
+// Check whether a polled file descriptor is ready to be read. A hang up or an error, e.g. after the device
+// was removed, is reported without POLLIN, but the read must still be done to report the end of file or
+// the error instead of polling again.
+fn poll_fd_readable(poll_fd: &nix::poll::PollFd) -> bool {
+	let readable = nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLHUP | nix::poll::PollFlags::POLLERR;
+	poll_fd.revents().is_some_and(|revents| revents.intersects(readable))
+}
+
+// Convert a null terminated string buffer filled by the kernel into a String with a single allocation
+fn string_from_c_buffer(buffer: &[u8]) -> String {
+	let len = buffer.iter().position(|&x| x == 0).unwrap_or(buffer.len());
+	String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
+
+// Check that the number of lines fits in a single handle request
+fn check_line_count(line_offset: &[u32]) -> io::Result<()> {
+	if line_offset.len() > gpio_ioctl::GPIOHANDLES_MAX {
+		return Err(GpioError::TooManyLines { requested: line_offset.len(), max: gpio_ioctl::GPIOHANDLES_MAX }.into());
+	}
+
+	Ok(())
+}
+
+// The kernel stores the consumer label in a 32 byte buffer which must be null terminated
+const CONSUMER_LABEL_MAX_LEN: usize = 31;
+
+// Convert the consumer label of a line request to the buffer passed to the kernel, rejecting labels
+// which don't fit instead of truncating them.
+fn consumer_label(label: &str) -> io::Result<[u8; 32]> {
+	if label.len() > CONSUMER_LABEL_MAX_LEN {
+		return Err(GpioError::LabelTooLong { len: label.len(), max: CONSUMER_LABEL_MAX_LEN }.into());
+	}
+
+	let mut consumer_label = [0; 32];
+	consumer_label[..label.len()].copy_from_slice(label.as_bytes());
+
+	Ok(consumer_label)
+}
+
+// Run a GPIO ioctl, retrying it when interrupted. With the log feature enabled every call is traced with
+// the ioctl name and the line offsets involved: successful calls at debug level and failures at warn level.
+fn gpio_ioctl_call<F: FnMut() -> nix::Result<i32>>(ioctl: &str, offsets: &[u32], call: F) -> io::Result<i32> {
+	let result = retry_on_interrupt(call);
+
+	#[cfg(feature = "log")]
+	match &result {
+		Ok(_) => log::debug!("gpio ioctl={} offsets={:?} result=ok", ioctl, offsets),
+		Err(e) => log::warn!("gpio ioctl={} offsets={:?} result=error errno={} error=\"{}\"", ioctl, offsets, e.raw_os_error().unwrap_or(0), e),
+	}
+
+	#[cfg(not(feature = "log"))]
+	let _ = (ioctl, offsets);
+
+	result
+}
+
+// The ioctls of the chip device which request lines. The chip builds the kernel requests and passes them
+// through this trait, so the mapping of the request options to the kernel flags can be tested against a mock
+// recording the requests instead of a real chip. On success the request holds the fd of the new handle.
+trait LineRequestIoctl {
+	fn get_line_handle(&self, request: &mut gpio_ioctl::GpioHandleRequest) -> io::Result<()>;
+	fn get_line_event(&self, request: &mut gpio_ioctl::GpioEventRequest) -> io::Result<()>;
+}
+
+impl LineRequestIoctl for File {
+	fn get_line_handle(&self, request: &mut gpio_ioctl::GpioHandleRequest) -> io::Result<()> {
+		let offsets = request.line_offsets[..request.lines as usize].to_vec();
+		gpio_ioctl_call("gpio_get_line_handle", &offsets, || unsafe { gpio_ioctl::gpio_get_line_handle(self.as_raw_fd(), request) })?;
+		Ok(())
 	}
+
+	fn get_line_event(&self, request: &mut gpio_ioctl::GpioEventRequest) -> io::Result<()> {
+		let offset = request.lineoffset;
+		gpio_ioctl_call("gpio_get_line_event", &[offset], || unsafe { gpio_ioctl::gpio_get_line_event(self.as_raw_fd(), request) })?;
+		Ok(())
+	}
+}
+
+#[cfg(feature = "tokio")]
+async fn async_sleep(duration: Duration) {
+	tokio::time::sleep(duration).await
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+async fn async_sleep(duration: Duration) {
+	async_std::task::sleep(duration).await
+}
+
+// Run a future for at most the given duration, returning None if it didn't complete in time
+#[cfg(feature = "tokio")]
+async fn async_timeout<F: std::future::Future>(duration: Duration, future: F) -> Option<F::Output> {
+	tokio::time::timeout(duration, future).await.ok()
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+async fn async_timeout<F: std::future::Future>(duration: Duration, future: F) -> Option<F::Output> {
+	async_std::future::timeout(duration, future).await.ok()
+}
+
+#[cfg(feature = "tokio")]
+async fn async_spawn_blocking<F: FnOnce() -> io::Result<()> + Send + 'static>(call: F) -> io::Result<()> {
+	tokio::task::spawn_blocking(call).await.map_err(io::Error::other)?
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+async fn async_spawn_blocking<F: FnOnce() -> io::Result<()> + Send + 'static>(call: F) -> io::Result<()> {
+	async_std::task::spawn_blocking(call).await
+}
+
+// Wait until any of the files becomes readable. The readiness is only a hint, the caller must
+// check the files without blocking before reading them.
+#[cfg(feature = "tokio")]
+async fn async_wait_readable(files: &[File]) -> io::Result<()> {
+	let async_fd = files.iter()
+		.map(|file| tokio::io::unix::AsyncFd::with_interest(file.as_raw_fd(), tokio::io::Interest::READABLE))
+		.collect::<io::Result<Vec<_>>>()?;
+
+	std::future::poll_fn(|cx| {
+		for fd in &async_fd {
+			if let std::task::Poll::Ready(ready) = fd.poll_read_ready(cx) {
+				ready?.clear_ready();
+				return std::task::Poll::Ready(Ok(()));
+			}
+		}
+		std::task::Poll::Pending
+	}).await
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+async fn async_wait_readable(files: &[File]) -> io::Result<()> {
+	// Async::new would switch the shared file description to non-blocking mode behind the back of the
+	// blocking read methods. The handle is only used for readiness, so its mode is left as it is.
+	let async_fd = files.iter()
+		.map(|file| async_io::Async::new_nonblocking(file.as_fd()))
+		.collect::<io::Result<Vec<_>>>()?;
+
+	std::future::poll_fn(|cx| {
+		for fd in &async_fd {
+			if let std::task::Poll::Ready(ready) = fd.poll_readable(cx) {
+				return std::task::Poll::Ready(ready);
+			}
+		}
+		std::task::Poll::Pending
+	}).await
 }
 
 mod gpio_ioctl {
@@ -60,7 +369,7 @@ mod gpio_ioctl {
 		pub consumer: [u8; 32],
 	}
 
-	const GPIOHANDLES_MAX: usize = 64;
+	pub const GPIOHANDLES_MAX: usize = 64;
 
 	#[repr(C)]
 	pub struct GpioHandleRequest {
@@ -95,6 +404,23 @@ mod gpio_ioctl {
 		pub fd: i32,
 	}
 
+	#[repr(C)]
+	pub struct GpioHandleConfig {
+		pub flags: u32,
+		pub default_values: [u8; GPIOHANDLES_MAX],
+		pub padding: [u32; 4],
+	}
+
+	impl Default for GpioHandleConfig {
+		fn default() -> Self {
+			Self {
+				flags: 0,
+				default_values: [0; GPIOHANDLES_MAX],
+				padding: [0; 4],
+			}
+		}
+	}
+
 	#[repr(C)]
 	pub struct GpioHandleData {
 		pub values: [u8; GPIOHANDLES_MAX],
@@ -108,29 +434,84 @@ mod gpio_ioctl {
 		}
 	}
 
+	// Opaque struct gpio_v2_line_info of the v2 ABI, only used to probe whether the kernel supports it.
+	// All zeros requests the info of line 0.
+	#[repr(C)]
+	pub struct GpioV2LineInfo {
+		pub data: [u8; GPIO_V2_LINE_INFO_SIZE],
+	}
+
+	pub const GPIO_V2_LINE_INFO_SIZE: usize = 256;
+
+	impl Default for GpioV2LineInfo {
+		fn default() -> Self {
+			Self {
+				data: [0; GPIO_V2_LINE_INFO_SIZE],
+			}
+		}
+	}
+
+	// Size of struct gpioline_info_changed (struct gpioline_info, u64 timestamp, u32 event_type and padding)
+	pub const GPIO_LINE_INFO_CHANGED_SIZE: usize = 104;
+
+	// Layout of struct gpioevent_data as read from the event file descriptor. Only its size is used, the
+	// record is decoded from bytes. The trailing padding depends on the alignment of u64, so the record
+	// is 16 bytes on x86_64 and arm but 12 bytes on i386.
+	#[repr(C)]
+	pub struct GpioEventData {
+		pub timestamp: u64,
+		pub id: u32,
+	}
+
+	pub const GPIO_EVENT_DATA_SIZE: usize = std::mem::size_of::<GpioEventData>();
+
 	const GPIO_MAGIC_NUMBER: u8 = 0xB4;
 	const GPIO_GET_CHIPINFO_IOCTL_COMMAND_NUMBER: u8 = 0x01;
 	const GPIO_GET_LINEINFO_IOCTL_COMMAND_NUMBER: u8 = 0x02;
 	const GPIO_GET_LINEHANDLE_IOCTL_COMMAND_NUMBER: u8 = 0x03;
 	const GPIO_GET_LINEEVENT_IOCTL_COMMAND_NUMBER: u8 = 0x04;
+	const GPIO_V2_GET_LINEINFO_IOCTL_COMMAND_NUMBER: u8 = 0x05;
 	const GPIO_GET_LINE_VALUES_IOCTL_COMMAND_NUMBER: u8 = 0x08;
 	const GPIO_SET_LINE_VALUES_IOCTL_COMMAND_NUMBER: u8 = 0x09;
+	const GPIO_SET_LINE_CONFIG_IOCTL_COMMAND_NUMBER: u8 = 0x0A;
+	const GPIO_GET_LINEINFO_WATCH_IOCTL_COMMAND_NUMBER: u8 = 0x0B;
+	const GPIO_GET_LINEINFO_UNWATCH_IOCTL_COMMAND_NUMBER: u8 = 0x0C;
 
 	ioctl_read!(gpio_get_chip_info, GPIO_MAGIC_NUMBER, GPIO_GET_CHIPINFO_IOCTL_COMMAND_NUMBER, GpioChipInfo);
 	ioctl_readwrite!(gpio_get_line_info, GPIO_MAGIC_NUMBER, GPIO_GET_LINEINFO_IOCTL_COMMAND_NUMBER, GpioLineInfo);
 	ioctl_readwrite!(gpio_get_line_handle, GPIO_MAGIC_NUMBER, GPIO_GET_LINEHANDLE_IOCTL_COMMAND_NUMBER, GpioHandleRequest);
 	ioctl_readwrite!(gpio_get_line_event, GPIO_MAGIC_NUMBER, GPIO_GET_LINEEVENT_IOCTL_COMMAND_NUMBER, GpioEventRequest);
+	ioctl_readwrite!(gpio_v2_get_line_info, GPIO_MAGIC_NUMBER, GPIO_V2_GET_LINEINFO_IOCTL_COMMAND_NUMBER, GpioV2LineInfo);
 	ioctl_readwrite!(gpio_get_line_values, GPIO_MAGIC_NUMBER, GPIO_GET_LINE_VALUES_IOCTL_COMMAND_NUMBER, GpioHandleData);
 	ioctl_readwrite!(gpio_set_line_values, GPIO_MAGIC_NUMBER, GPIO_SET_LINE_VALUES_IOCTL_COMMAND_NUMBER, GpioHandleData);
+	ioctl_readwrite!(gpio_set_line_config, GPIO_MAGIC_NUMBER, GPIO_SET_LINE_CONFIG_IOCTL_COMMAND_NUMBER, GpioHandleConfig);
+	ioctl_readwrite!(gpio_watch_line_info, GPIO_MAGIC_NUMBER, GPIO_GET_LINEINFO_WATCH_IOCTL_COMMAND_NUMBER, GpioLineInfo);
+	ioctl_readwrite!(gpio_unwatch_line_info, GPIO_MAGIC_NUMBER, GPIO_GET_LINEINFO_UNWATCH_IOCTL_COMMAND_NUMBER, u32);
 
 }
 
+// The handle types are documented as Send and Sync, make sure a future change doesn't silently break it.
+const _: fn() = || {
+	fn is_send_and_sync<T: Send + Sync>() {}
+	is_send_and_sync::<GpioChip>();
+	is_send_and_sync::<GpioLineValue>();
+	is_send_and_sync::<GpioLineInfo>();
+	is_send_and_sync::<GpioLineEvent>();
+	is_send_and_sync::<GpioLineWriter>();
+	is_send_and_sync::<GpioLineReader>();
+	is_send_and_sync::<GpioLineBank>();
+	is_send_and_sync::<GpioNamedLineEvent>();
+};
+
 // **************** Flags for line state **************
 const GPIOLINE_FLAG_KERNEL: u32 = 1 << 0;
 const GPIOLINE_FLAG_IS_OUT: u32 = 1 << 1;
 const GPIOLINE_FLAG_ACTIVE_LOW: u32 = 1 << 2;
 const GPIOLINE_FLAG_OPEN_DRAIN: u32 = 1 << 3;
 const GPIOLINE_FLAG_OPEN_SOURCE: u32  = 1 << 4;
+const GPIOLINE_FLAG_BIAS_PULL_UP: u32 = 1 << 5;
+const GPIOLINE_FLAG_BIAS_PULL_DOWN: u32 = 1 << 6;
+const GPIOLINE_FLAG_BIAS_DISABLE: u32 = 1 << 7;
 
 // **************** Flags for line requests ***************
 const GPIOHANDLE_REQUEST_INPUT: u32 = 1 << 0;
@@ -138,17 +519,50 @@ const GPIOHANDLE_REQUEST_OUTPUT: u32 = 	1 << 1;
 const GPIOHANDLE_REQUEST_ACTIVE_LOW: u32 = 1 << 2;
 const GPIOHANDLE_REQUEST_OPEN_DRAIN: u32 = 1 << 3;
 const GPIOHANDLE_REQUEST_OPEN_SOURCE: u32 = 1 << 4;
+const GPIOHANDLE_REQUEST_BIAS_PULL_UP: u32 = 1 << 5;
+const GPIOHANDLE_REQUEST_BIAS_PULL_DOWN: u32 = 1 << 6;
+const GPIOHANDLE_REQUEST_BIAS_DISABLE: u32 = 1 << 7;
+
+// **************** Flags for event requests ***************
+const GPIOEVENT_REQUEST_RISING_EDGE: u32 = 1 << 0;
+const GPIOEVENT_REQUEST_FALLING_EDGE: u32 = 1 << 1;
+const GPIOEVENT_REQUEST_BOTH_EDGES: u32 = GPIOEVENT_REQUEST_RISING_EDGE | GPIOEVENT_REQUEST_FALLING_EDGE;
+
+// **************** Line info change identifiers ***************
+const GPIOLINE_CHANGED_REQUESTED: u32 = 1;
+const GPIOLINE_CHANGED_RELEASED: u32 = 2;
+const GPIOLINE_CHANGED_CONFIG: u32 = 3;
+
+// **************** Event identifiers ***************
+const GPIOEVENT_EVENT_RISING_EDGE: u32 = 0x01;
+const GPIOEVENT_EVENT_FALLING_EDGE: u32 = 0x02;
 
 /// Represents a Linux chardev GPIO chip interface.
 /// It can be used to get information about the chip and lines and 
 /// to request GPIO lines that can be used as output or input.
+/// It is *Send* and *Sync* so it can be moved to or shared between threads.
 pub struct GpioChip {
 	name: String,
 	label: String,
 	num_lines: u32,
+	device_number: (u32, u32),
+	sysfs_path: PathBuf,
+	line_info_cache: Mutex<HashMap<u32, GpioLineInfo>>,
+	path: Option<PathBuf>,
+	// Whether the chip was checked against sysfs when opened, so that *reopen* opens it the same way
+	sysfs_checked: bool,
 	fd: File,
 }
 
+/// Gives access to the GPIO chip file descriptor, e.g. to issue ioctls not wrapped by this crate. The
+/// file descriptor remains owned by the GpioChip: it must not be closed nor left in a state incompatible
+/// with the operations of this crate.
+impl AsRawFd for GpioChip {
+	fn as_raw_fd(&self) -> RawFd {
+		self.fd.as_raw_fd()
+	}
+}
+
 impl fmt::Display for GpioChip {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		write!(f, "{} [{}] ({} lines)", self.name, self.label, self.num_lines)
@@ -156,6 +570,7 @@ impl fmt::Display for GpioChip {
 }
 
 /// Represents the direction of a GPIO line. Possible values are *Input* and *Output*.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineDirection {
 	Input,
 	Output,
@@ -171,6 +586,7 @@ impl fmt::Display for LineDirection {
 }
 
 /// Represents the active state condition of a line. Possible values are *Active High* or *Active Low*.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineActiveState {
 	ActiveLow,
 	ActiveHigh,
@@ -186,6 +602,7 @@ impl fmt::Display for LineActiveState {
 }
 
 /// Represents the output mode of a GPIO line. Possible values are *Open Drain* and *Open Source*.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputMode {
 	None,
 	OpenDrain,
@@ -202,317 +619,3308 @@ impl fmt::Display for OutputMode {
 	}
 }
 
-/// Represents the information of a specific GPIO line. Can only be obtained through the GpioChip interface.
-pub struct GpioLineInfo {
-	direction: LineDirection,
-	active_state: LineActiveState,
-	used: bool,
-	open_drain: bool,
-	open_source: bool,
-	name: String,
-	consumer: String,
+bitflags! {
+	/// Represents the set of flags reported by the kernel in the information of a GPIO line.
+	pub struct LineFlags: u32 {
+		const USED = GPIOLINE_FLAG_KERNEL;
+		const OUTPUT = GPIOLINE_FLAG_IS_OUT;
+		const ACTIVE_LOW = GPIOLINE_FLAG_ACTIVE_LOW;
+		const OPEN_DRAIN = GPIOLINE_FLAG_OPEN_DRAIN;
+		const OPEN_SOURCE = GPIOLINE_FLAG_OPEN_SOURCE;
+		const BIAS_PULL_UP = GPIOLINE_FLAG_BIAS_PULL_UP;
+		const BIAS_PULL_DOWN = GPIOLINE_FLAG_BIAS_PULL_DOWN;
+		const BIAS_DISABLE = GPIOLINE_FLAG_BIAS_DISABLE;
+	}
 }
 
-pub struct GpioLineValue {
-	parent_chip_name: String,
-	direction: LineDirection,
-	offset: Vec<u32>,
-	fd: File,
+/// Represents the bias of an input line. Possible values are *Pull Up*, *Pull Down* and *Disabled*. With
+/// *None* the bias of the line is left as configured by the platform. Requires Linux 5.5 or later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineBias {
+	None,
+	PullUp,
+	PullDown,
+	Disabled,
 }
 
-impl GpioLineValue {
-	/// Get the value of GPIO lines. The values can only be read if the lines have previously been
-	/// requested as either inputs, using the *request_line_values_input* method, or outputs using 
-	/// the *request_line_values_output*. The input vector in both the *request* and get functions
-	/// must match exactly, otherwise the correct file descriptor needed to access the
-	/// lines can not be retrieved and the function will fail.
-	pub fn get_line_value(&self) -> io::Result<Vec<u8>>{
-		let mut data = gpio_ioctl::GpioHandleData::default();
+impl fmt::Display for LineBias {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LineBias::PullUp => write!(f, "Pull up"),
+			LineBias::PullDown => write!(f, "Pull down"),
+			LineBias::Disabled => write!(f, "Bias disabled"),
+			LineBias::None => write!(f, ""),
+		}
+	}
+}
 
-		unsafe {
-			convert_nix_to_io_result(gpio_ioctl::gpio_get_line_values(self.fd.as_raw_fd(), &mut data))?;
+impl LineBias {
+	fn request_flags(&self) -> u32 {
+		match self {
+			LineBias::PullUp => GPIOHANDLE_REQUEST_BIAS_PULL_UP,
+			LineBias::PullDown => GPIOHANDLE_REQUEST_BIAS_PULL_DOWN,
+			LineBias::Disabled => GPIOHANDLE_REQUEST_BIAS_DISABLE,
+			LineBias::None => 0,
 		}
+	}
+}
 
-		let mut output_data : Vec<u8> = Vec::with_capacity(self.offset.len());
+/// Represents how the values of a line are interpreted. *Logical* values are the ones returned by the
+/// kernel, inverted for active low lines, and are the default. *Physical* values are the electrical
+/// levels of the lines regardless of the active low configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Polarity {
+	#[default]
+	Logical,
+	Physical,
+}
 
-		for index in 0..self.offset.len() {
-			output_data.push(data.values[index]);
-		}
+/// Represents the request of a group of lines as inputs or outputs, as used by *request_batch* and
+/// *request_with_retry*. The
+/// fields match the arguments of *request_line_values_input* and *request_line_values_output*.
+pub enum LineRequest {
+	Input {
+		line_offset: Vec<u32>,
+		active_low: bool,
+		label: String,
+	},
+	Output {
+		line_offset: Vec<u32>,
+		output_mode: OutputMode,
+		active_low: bool,
+		label: String,
+	},
+}
 
-		Ok(output_data)
+/// Represents the configuration of a request made with *GpioChip::request*. The presence of
+/// *initial_values* decides the direction: with them the lines are requested as outputs driven to those
+/// values, in the same order as the requested lines, and without them as inputs. The *output_mode* only
+/// applies to outputs and the *bias* only to inputs: giving either for the other direction fails the
+/// request with *InvalidInput* instead of being ignored. The default configuration requests active high
+/// inputs with the bias left as configured by the platform, push-pull output mode and an empty label, so
+/// only what differs needs to be given, e.g. *RequestConfig { bias: LineBias::PullUp, ..Default::default() }*.
+#[derive(Debug, Clone)]
+pub struct RequestConfig {
+	pub initial_values: Option<Vec<u8>>,
+	pub output_mode: OutputMode,
+	pub bias: LineBias,
+	pub active_low: bool,
+	pub label: String,
+}
+
+impl Default for RequestConfig {
+	fn default() -> Self {
+		RequestConfig {
+			initial_values: None,
+			output_mode: OutputMode::None,
+			bias: LineBias::None,
+			active_low: false,
+			label: String::new(),
+		}
 	}
+}
 
-	/// Set the value of GPIO lines. The value can only be set if the lines have previously been
-	/// requested as outputs using the *request_line_values_output*. The input vector in both
-	/// functions must match exactly, otherwise the correct file descriptor needed to access the
-	/// lines can not be retrieved and the function will fail.
-	pub fn set_line_value(&self, value: u8) -> io::Result<()>{
-		let mut data = gpio_ioctl::GpioHandleData::default();
+/// Represents how a request is retried while its lines are busy, as used by *request_with_retry*. The
+/// wait after the first failed attempt is *backoff* and it doubles after every further attempt up to
+/// *max_backoff*. The default makes 5 attempts starting with a 10 ms wait, capped at 1 s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	pub max_attempts: u32,
+	pub backoff: Duration,
+	pub max_backoff: Duration,
+}
 
-		for line_index in 0..self.offset.len() {
-				data.values[line_index] = value;
-		}
+impl RetryPolicy {
+	/// Get the time to wait after the failed attempt number *attempt*, starting at 1.
+	pub fn delay(&self, attempt: u32) -> Duration {
+		let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+		self.backoff.checked_mul(factor).unwrap_or(self.max_backoff).min(self.max_backoff)
+	}
+}
 
-		unsafe {
-			convert_nix_to_io_result(gpio_ioctl::gpio_set_line_values(self.fd.as_raw_fd(), &mut data))?;
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		RetryPolicy {
+			max_attempts: 5,
+			backoff: Duration::from_millis(10),
+			max_backoff: Duration::from_secs(1),
 		}
-
-		Ok(())
 	}
+}
 
-	pub fn parent_chip_name(&self) -> &str {
-		&self.parent_chip_name
+/// Represents the GPIO chip part of a line specification. It can be given as the chip number (*0*),
+/// the chip device name (*gpiochip0*) or the full device path (*/dev/gpiochip0*).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChipRef {
+	Number(u32),
+	Name(String),
+	Path(PathBuf),
+}
+
+impl ChipRef {
+	/// Get the path of the GPIO chip device which can be used to open it with *GpioChip::new*.
+	pub fn path(&self) -> PathBuf {
+		match self {
+			ChipRef::Number(number) => dev_root().join(format!("gpiochip{}", number)),
+			ChipRef::Name(name) => dev_root().join(name),
+			ChipRef::Path(path) => path.clone(),
+		}
 	}
+}
 
-	pub fn direction(&self) -> &LineDirection {
-		&self.direction
+/// Represents a line in a line specification, selected either by its offset or by its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineSelector {
+	Offset(u32),
+	Name(String),
+}
+
+/// Represents the configuration this crate was built with, as returned by *build_info*, e.g. to be logged
+/// in support requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+	/// Version of the crate.
+	pub version: &'static str,
+	/// GPIO character device ABI used to talk to the kernel, always *v1*.
+	pub abi: &'static str,
+	/// Async runtime used by the async functions, *tokio* or *async-std*, or *None* without async support.
+	pub async_runtime: Option<&'static str>,
+	/// Whether the ioctl calls are traced with the *log* feature.
+	pub log: bool,
+}
+
+/// Get the configuration this crate was built with. If both the *tokio* and *async-std* features are
+/// enabled, tokio is the runtime in use.
+pub fn build_info() -> BuildInfo {
+	let async_runtime = if cfg!(feature = "tokio") {
+		Some("tokio")
+	} else if cfg!(feature = "async-std") {
+		Some("async-std")
+	} else {
+		None
+	};
+
+	BuildInfo {
+		version: env!("CARGO_PKG_VERSION"),
+		abi: "v1",
+		async_runtime,
+		log: cfg!(feature = "log"),
 	}
 }
 
-impl fmt::Display for GpioLineInfo {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "\t {}", self.direction)?;
-		if self.used {
-			write!(f, "\t Used")?;
-		}
-		else {
-			write!(f, "\t Unused")?;
-		}
-		if self.consumer.is_empty() {
-			write!(f, "\t Unnamed")?;
+/// Parse a line specification of the form *chip:line,line,...* as accepted by the command line tools,
+/// e.g. *gpiochip0:5,7-9,GPIO3*. Lines are given as offsets, ranges of offsets (inclusive) or names.
+/// The lines given by name can be converted to offsets using *GpioChip::resolve*. Ranges longer than the
+/// 65536 lines a chip can have are rejected.
+pub fn parse_line_spec(spec: &str) -> io::Result<(ChipRef, Vec<LineSelector>)> {
+	let invalid_spec = || Error::new(ErrorKind::InvalidInput, format!("Invalid line specification \"{}\"", spec));
+
+	let (chip, lines) = spec.split_once(':').ok_or_else(invalid_spec)?;
+
+	let chip = if chip.is_empty() {
+		return Err(invalid_spec());
+	} else if let Ok(number) = chip.parse() {
+		ChipRef::Number(number)
+	} else if chip.starts_with('/') {
+		ChipRef::Path(PathBuf::from(chip))
+	} else {
+		ChipRef::Name(chip.to_string())
+	};
+
+	let mut selectors = Vec::new();
+
+	for line in lines.split(',').map(str::trim) {
+		if line.is_empty() {
+			return Err(invalid_spec());
 		}
-		else {
-			write!(f,"\t {}", self.consumer)?;
-		}
-		write!(f,"\t {}", self.active_state())?;
-		if self.open_drain {
-			write!(f,"\t Open drain")?;
+
+		if let Ok(offset) = line.parse() {
+			selectors.push(LineSelector::Offset(offset));
+		} else if let Some((Ok(first), Ok(last))) = line.split_once('-').map(|(first, last)| (first.parse::<u32>(), last.parse::<u32>())) {
+			// The kernel stores the number of lines of a chip in 16 bits, so a longer range can never be valid
+			if first > last || last - first > u16::MAX as u32 {
+				return Err(invalid_spec());
+			}
+			selectors.extend((first..=last).map(LineSelector::Offset));
+		} else {
+			selectors.push(LineSelector::Name(line.to_string()));
 		}
-		else if self.open_source {
-			write!(f,"\t Open source")?;
+	}
+
+	Ok((chip, selectors))
+}
+
+/// Represents the edges for which events are generated on a line requested using *request_line_events*.
+#[derive(Clone, Copy)]
+pub enum EdgeDetection {
+	RisingEdge,
+	FallingEdge,
+	BothEdges,
+}
+
+/// Represents the edge which triggered a GPIO line event. Possible values are *Rising* and *Falling*.
+/// Event identifiers not known to this crate, which newer kernels may report, are returned as *Unknown*
+/// with the raw identifier instead of failing the event read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEdge {
+	Rising,
+	Falling,
+	Unknown(u32),
+}
+
+impl fmt::Display for LineEdge {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LineEdge::Rising => write!(f, "Rising edge"),
+			LineEdge::Falling => write!(f, "Falling edge"),
+			LineEdge::Unknown(id) => write!(f, "Unknown event {}", id),
 		}
+	}
+}
 
-		Ok(())
+/// Represents the text format of the events written by *GpioLineEvent::capture_to*: comma or tab
+/// separated values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+	Csv,
+	Tsv,
+}
+
+impl CaptureFormat {
+	fn separator(&self) -> char {
+		match self {
+			CaptureFormat::Csv => ',',
+			CaptureFormat::Tsv => '\t',
+		}
 	}
 }
 
-impl GpioLineInfo {
-	pub fn direction(&self) -> &LineDirection {
-		&self.direction
+/// Represents an edge event read from a set of lines requested using *request_line_events*.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpioEvent {
+	offset: u32,
+	edge: LineEdge,
+	timestamp: u64,
+}
+
+impl fmt::Display for GpioEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "[{}.{:09}] Line {}: {}", self.timestamp / 1_000_000_000, self.timestamp % 1_000_000_000, self.offset, self.edge)
 	}
+}
 
-	pub fn active_state(&self) -> &LineActiveState {
-		&self.active_state
+impl GpioEvent {
+	/// Get the offset of the line which generated the event.
+	pub fn offset(&self) -> u32 {
+		self.offset
 	}
 
-	pub fn is_used(&self) -> &bool {
-		&self.used
+	/// Get the edge which generated the event.
+	pub fn edge(&self) -> &LineEdge {
+		&self.edge
 	}
 
-	pub fn is_open_drain(&self) -> &bool {
-		&self.open_drain
+	/// Check whether two events were generated by the same edge on the same line, ignoring their timestamps.
+	pub fn same_transition(&self, other: &GpioEvent) -> bool {
+		self.offset == other.offset && self.edge == other.edge
 	}
 
-	pub fn is_open_source(&self) -> &bool {
-		&self.open_source
+	/// Check whether this event happened before *other*. The v1 interface has no sequence numbers, so the
+	/// order is given by the timestamps, with ties between lines broken by offset for a stable order.
+	pub fn precedes(&self, other: &GpioEvent) -> bool {
+		self.cmp_order(other) == std::cmp::Ordering::Less
 	}
 
-	pub fn name(&self) -> &str {
-		&self.name
+	/// Compare two events by the order in which they happened, as *precedes* does. This restores the
+	/// hardware order of events read from several lines with *events.sort_by(GpioEvent::cmp_order)*.
+	pub fn cmp_order(&self, other: &GpioEvent) -> std::cmp::Ordering {
+		(self.timestamp, self.offset).cmp(&(other.timestamp, other.offset))
 	}
 
-	pub fn consumer(&self) -> &str {
-		&self.consumer
+	/// Get the kernel timestamp of the event in nanoseconds. The GPIO chardev v1 interface used by this
+	/// crate does not allow selecting the event clock: since Linux 5.7 the timestamp is taken from
+	/// CLOCK_MONOTONIC (time since boot) while older kernels use CLOCK_REALTIME (time since the Unix epoch).
+	pub fn timestamp(&self) -> u64 {
+		self.timestamp
 	}
 }
 
-impl GpioChip {
+/// Represents a set of GPIO lines requested from a GpioChip as inputs with edge detection. The kernel
+/// provides a separate file descriptor for each of the lines and the events of all of them are
+/// read through this object. The lines are held until this object is dropped.
+pub struct GpioLineEvent {
+	parent_chip_name: String,
+	consumer: String,
+	offset: Vec<u32>,
+	fd: Vec<File>,
+	events_read: u64,
+	nonblocking: bool,
+}
 
-	/// Create a new GPIO chip interface.
-	pub fn new(path: &dyn AsRef<Path>) -> io::Result<GpioChip> {
-		let dev_file = OpenOptions::new().read(true).write(true).open(path)?;
+impl GpioLineEvent {
+	/// Wait for the next event on any of the requested lines and return it. This function blocks
+	/// until an event is available. Interruptions by signals are retried internally so the function
+	/// only returns once an event is read or a real error occurs. If the lines were set to non-blocking
+	/// mode with *set_nonblocking*, the function fails with *WouldBlock* instead of waiting.
+	pub fn read_event(&mut self) -> io::Result<GpioEvent> {
+		if self.nonblocking {
+			return match self.poll_event(0)? {
+				Some(index) => self.read_event_from(index),
+				None => Err(io::Error::from(ErrorKind::WouldBlock)),
+			};
+		}
+
+		loop {
+			if let Some(index) = self.poll_event(-1)? {
+				return self.read_event_from(index);
+			}
+		}
+	}
 
-		GpioChip::is_gpiochip_cdev(path)?;
+	/// Wait for the next event on any of the requested lines until the absolute *deadline* and return it,
+	/// or *None* if no event arrived in time. Unlike a relative timeout, calling this repeatedly with
+	/// deadlines at fixed instants doesn't accumulate drift. A deadline already in the past returns *None*
+	/// right away without a system call.
+	pub fn read_event_deadline(&mut self, deadline: Instant) -> io::Result<Option<GpioEvent>> {
+		match self.poll_event_until(deadline)? {
+			Some(index) => self.read_event_from(index).map(Some),
+			None => Ok(None),
+		}
+	}
 
-		let mut gpio_chip_info = gpio_ioctl::GpioChipInfo::default();
+	/// Write the events of the requested lines to *writer* until *until* returns true, e.g. to record a
+	/// capture for later analysis, and return the number of events written. A header line is written
+	/// first and then one line per event with the timestamp in nanoseconds, the line offset and the edge
+	/// (*rising*, *falling* or *unknown-<id>*), separated as given by *format*. The output is buffered and
+	/// flushed at least every 100 ms, even while events keep arriving, and when the capture stops. *until*
+	/// is checked after every event and at least every 100 ms while the lines are idle.
+	pub fn capture_to<W: Write>(&mut self, writer: W, format: CaptureFormat, until: impl Fn() -> bool) -> io::Result<u64> {
+		const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+		let mut writer = io::BufWriter::new(writer);
+		let separator = format.separator();
+		let mut events_written = 0;
+
+		writeln!(writer, "timestamp_ns{}line{}edge", separator, separator)?;
+		let mut last_flush = Instant::now();
+
+		while !until() {
+			if let Some(index) = self.poll_event_until(last_flush + FLUSH_INTERVAL)? {
+				let event = self.read_event_from(index)?;
+				let edge = match event.edge {
+					LineEdge::Rising => String::from("rising"),
+					LineEdge::Falling => String::from("falling"),
+					LineEdge::Unknown(id) => format!("unknown-{}", id),
+				};
+				writeln!(writer, "{}{}{}{}{}", event.timestamp, separator, event.offset, separator, edge)?;
+				events_written += 1;
+			}
 
-		unsafe { 
-			convert_nix_to_io_result(gpio_ioctl::gpio_get_chip_info(dev_file.as_raw_fd(), &mut gpio_chip_info))?;
+			// Flush on a fixed period rather than only when idle, so a steady stream of events still reaches the writer
+			if last_flush.elapsed() >= FLUSH_INTERVAL {
+				writer.flush()?;
+				last_flush = Instant::now();
+			}
 		}
 
-		Ok (GpioChip{
-				name: String::from_utf8(gpio_chip_info.name.to_vec()).unwrap().trim_end_matches(char::from(0)).to_string(),
-				label: String::from_utf8(gpio_chip_info.label.to_vec()).unwrap().trim_end_matches(char::from(0)).to_string(),
-				num_lines: gpio_chip_info.lines,
-				fd: dev_file,})
+		writer.flush()?;
+
+		Ok(events_written)
 	}
 
-	fn is_gpiochip_cdev(path: &dyn AsRef<Path>) -> io::Result<bool>{
-		const LINE_FEED : u8 = 10;
+	/// Read events in a loop and pass each of them to *callback* until it returns *ControlFlow::Break*.
+	/// Any error reading the events ends the loop and is returned. In non-blocking mode the *WouldBlock*
+	/// error of *read_event* also ends the loop, so this is meant to be used with blocking lines.
+	pub fn run_events<F: FnMut(GpioEvent) -> ControlFlow<()>>(&mut self, mut callback: F) -> io::Result<()> {
+		loop {
+			if callback(self.read_event()?).is_break() {
+				return Ok(());
+			}
+		}
+	}
 
-		/*rv = lstat(path, &statbuf);*/
-		let file_metadata = symlink_metadata(path)?; 
+	/// Asynchronous version of *read_event* which waits for the event using the reactor of the selected
+	/// async runtime (*tokio* or *async-std* features). The event is only read once it is available so
+	/// dropping the returned future never loses an event.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn read_event_async(&mut self) -> io::Result<GpioEvent> {
+		loop {
+			if let Some(index) = self.poll_event(0)? {
+				return self.read_event_from(index);
+			}
 
-		/*if (!S_ISCHR(statbuf.st_mode)) */
-		if !file_metadata.file_type().is_char_device() { 
-			return Err(Error::new(ErrorKind::InvalidInput,"File is not character device"));
+			async_wait_readable(&self.fd).await?;
 		}
+	}
 
-		/*basename(pathcpy);*/
-		let basename = path.as_ref().file_name().unwrap(); 
+	/// Version of *read_event_async* which gives up after *duration* and returns *None* if no event arrived
+	/// in time. Events are only read once available, so a timeout never leaves an event partially read.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn read_event_async_timeout(&mut self, duration: Duration) -> io::Result<Option<GpioEvent>> {
+		async_timeout(duration, self.read_event_async()).await.transpose()
+	}
 
-		let sysfs = format!{"/sys/bus/gpio/devices/{}/dev", basename.to_str().unwrap()};
+	/// Read, without blocking, all the events currently queued by the kernel for the requested lines. The
+	/// number of events returned gives an idea of how full the kernel event buffers were, which helps tuning
+	/// how often the events need to be read.
+	pub fn drain_events(&mut self) -> io::Result<Vec<GpioEvent>> {
+		let mut events = Vec::new();
 
-		/*if (access(sysfsp, R_OK) != 0)*/
-		if !Path::new(&sysfs).is_file() /*I check if it is a file instead of read access done in libgpiod */ {
-			return  Err(Error::new(ErrorKind::InvalidInput,"Matching GPIO in sys not found"));
+		while let Some(index) = self.poll_event(0)? {
+			events.push(self.read_event_from(index)?);
 		}
 
-		let mut sysfs_rdev: [u8; 16] = [0; 16];
-		{
-			let mut fd = OpenOptions::new().read(true).open(sysfs)?;
+		Ok(events)
+	}
+
+	/// Set the event file descriptors of the lines to non-blocking (*O_NONBLOCK*) or blocking mode. In
+	/// non-blocking mode *read_event* returns *WouldBlock* when no event is pending, which suits poll based
+	/// event loops. The asynchronous functions are not affected since they only read available events.
+	pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+		for fd in &self.fd {
+			let flags = convert_nix_to_io_result(nix::fcntl::fcntl(fd.as_raw_fd(), nix::fcntl::FcntlArg::F_GETFL))?;
+			let mut flags = nix::fcntl::OFlag::from_bits_truncate(flags);
+
+			flags.set(nix::fcntl::OFlag::O_NONBLOCK, nonblocking);
 
-			fd.read(&mut sysfs_rdev)?; /*Ignoring any error for now*/
+			convert_nix_to_io_result(nix::fcntl::fcntl(fd.as_raw_fd(), nix::fcntl::FcntlArg::F_SETFL(flags)))?;
 		}
 
-		let lf_pos = sysfs_rdev.iter().position(|&x| x == LINE_FEED).unwrap_or(sysfs_rdev.len()-1);
+		self.nonblocking = nonblocking;
+
+		Ok(())
+	}
 
-		let file_rdev = format!("{}:{}", file_metadata.rdev() >> 8, file_metadata.rdev() & 0xFF);
+	/// Check without blocking whether an event is available on any of the requested lines. The event is
+	/// not consumed and can be read afterwards with *read_event*.
+	pub fn has_pending_event(&self) -> io::Result<bool> {
+		Ok(self.poll_event(0)?.is_some())
+	}
 
-		if String::from_utf8(sysfs_rdev[0 .. lf_pos-1].to_vec()).unwrap() == file_rdev {
-			return Err(Error::new(ErrorKind::Other,"Unmatched device versions"));
+	/// Count the edges seen on each of the requested lines during *window*, e.g. to measure the frequency of
+	/// a signal or the speed of a fan. Every requested line is present in the returned map, with a count of
+	/// zero if it had no edges. Events are read until the window elapses, so edges arriving afterwards are
+	/// left queued for the next call. This works both in blocking and non-blocking mode.
+	pub fn count_edges(&mut self, window: Duration) -> io::Result<HashMap<u32, u32>> {
+		let deadline = Instant::now() + window;
+		let mut counts : HashMap<u32, u32> = self.offset.iter().map(|&offset| (offset, 0)).collect();
+
+		while let Some(index) = self.poll_event_until(deadline)? {
+			let event = self.read_event_from(index)?;
+			*counts.entry(event.offset).or_insert(0) += 1;
 		}
 
-		Ok(true)
+		Ok(counts)
 	}
 
-	/// Request the info of a specific GPIO line.
-	pub fn get_line_info(&self, line_number: &u32) -> io::Result<GpioLineInfo>{
-		let mut gpio_line_info = gpio_ioctl::GpioLineInfo::default();
+	// Poll the event file descriptors until the deadline and return the index of the first line with an
+	// event available. A deadline in the past returns right away without a system call.
+	fn poll_event_until(&self, deadline: Instant) -> io::Result<Option<usize>> {
+		loop {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Ok(None);
+			}
+
+			// Round up so that poll doesn't return just before the deadline and spin. An interrupted poll
+			// is not restarted with the same timeout but with the time remaining until the deadline.
+			let timeout = remaining.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32;
+			match self.poll_event_once(timeout) {
+				Ok(Some(index)) => return Ok(Some(index)),
+				Ok(None) => (),
+				Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	// Poll the event file descriptors for the given timeout in milliseconds (-1 waits forever) and
+	// return the index of the first line with an event available.
+	fn poll_event(&self, timeout: i32) -> io::Result<Option<usize>> {
+		loop {
+			match self.poll_event_once(timeout) {
+				Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+				result => return result,
+			}
+		}
+	}
 
-		gpio_line_info.line_offset = *line_number;
+	// Same as poll_event but a poll interrupted by a signal fails with Interrupted instead of being retried
+	fn poll_event_once(&self, timeout: i32) -> io::Result<Option<usize>> {
+		let mut poll_fd : Vec<nix::poll::PollFd> = self.fd.iter()
+			.map(|fd| nix::poll::PollFd::new(fd.as_raw_fd(), nix::poll::PollFlags::POLLIN))
+			.collect();
 
-		unsafe { 
-			convert_nix_to_io_result(gpio_ioctl::gpio_get_line_info(self.fd.as_raw_fd(), &mut gpio_line_info))?;
+		if convert_nix_to_io_result(nix::poll::poll(&mut poll_fd, timeout))? == 0 {
+			return Ok(None);
 		}
 
-		let direction = if gpio_line_info.flags & GPIOLINE_FLAG_IS_OUT == GPIOLINE_FLAG_IS_OUT {
-			LineDirection::Output
-		} else {
-			LineDirection::Input
-		};
+		poll_fd.iter()
+			.position(poll_fd_readable)
+			.map(Some)
+			.ok_or_else(|| Error::other("Poll returned without any event available"))
+	}
 
-		let active_state = if gpio_line_info.flags & GPIOLINE_FLAG_ACTIVE_LOW == GPIOLINE_FLAG_ACTIVE_LOW {
-			LineActiveState::ActiveLow
-		} else {
-			LineActiveState::ActiveHigh
+	fn read_event_from(&mut self, index: usize) -> io::Result<GpioEvent> {
+		let mut data = [0u8; gpio_ioctl::GPIO_EVENT_DATA_SIZE];
+
+		read_record(&self.fd[index], &mut data)?;
+		self.events_read += 1;
+
+		let mut timestamp = [0u8; 8];
+		timestamp.copy_from_slice(&data[0..8]);
+		let mut id = [0u8; 4];
+		id.copy_from_slice(&data[8..12]);
+
+		let edge = match u32::from_ne_bytes(id) {
+			GPIOEVENT_EVENT_RISING_EDGE => LineEdge::Rising,
+			GPIOEVENT_EVENT_FALLING_EDGE => LineEdge::Falling,
+			id => LineEdge::Unknown(id),
 		};
 
-		let used = (gpio_line_info.flags & GPIOLINE_FLAG_KERNEL) == GPIOLINE_FLAG_KERNEL;
-		let open_drain = (gpio_line_info.flags & GPIOLINE_FLAG_OPEN_DRAIN) == GPIOLINE_FLAG_OPEN_DRAIN; 
-		let open_source = (gpio_line_info.flags & GPIOLINE_FLAG_OPEN_SOURCE) == GPIOLINE_FLAG_OPEN_SOURCE;
-		let name = String::from_utf8(gpio_line_info.name.to_vec()).unwrap().trim_end_matches(char::from(0)).to_string();
-		let consumer = String::from_utf8(gpio_line_info.consumer.to_vec()).unwrap().trim_end_matches(char::from(0)).to_string();
-		
-		Ok(GpioLineInfo{
-			direction,
-			active_state,
-			used,
-			open_drain,
-			open_source,
-			name,
-			consumer,
+		Ok(GpioEvent {
+			offset: self.offset[index],
+			edge,
+			timestamp: u64::from_ne_bytes(timestamp),
 		})
 	}
 
-	/// Request the GPIO chip to configure the lines passed as argument as outputs. Calling this
-	/// operation is a precondition to being able to set the state of the GPIO lines. All the lines
-	/// passed in one request must share the output mode and the active state. The state of lines configured
-	/// as outputs can also be read using the *get_line_value* method.
-	pub fn request_line_values_output(&self, line_offset: &Vec<u32>, output_mode: OutputMode, active_low: bool, label: &str) -> io::Result<GpioLineValue> {
-		let mut gpio_handle_request = gpio_ioctl::GpioHandleRequest::default();
+	pub fn parent_chip_name(&self) -> &str {
+		&self.parent_chip_name
+	}
 
-		gpio_handle_request.lines = line_offset.len() as u32;
+	/// Get the consumer label the lines were requested with, as reported by *get_line_info* for them.
+	pub fn consumer(&self) -> &str {
+		&self.consumer
+	}
 
-		for index in 0..line_offset.len() {
-			gpio_handle_request.line_offsets[index] = line_offset[index];
-		}
-		
-		gpio_handle_request.flags |= GPIOHANDLE_REQUEST_OUTPUT;
-		
-		match output_mode {
-			OutputMode::OpenDrain => gpio_handle_request.flags |= GPIOHANDLE_REQUEST_OPEN_DRAIN,
-			OutputMode::OpenSource => gpio_handle_request.flags |= GPIOHANDLE_REQUEST_OPEN_SOURCE,
-			_ => (),
-		};
+	/// Get the total number of events read from the requested lines since they were requested.
+	pub fn events_read(&self) -> u64 {
+		self.events_read
+	}
 
-		if active_low {
-			gpio_handle_request.flags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
-		}
+	/// Get the offsets of the lines requested for events.
+	pub fn offset(&self) -> &[u32] {
+		&self.offset
+	}
 
-		if label.len() > 32 {
-			return Err(io::Error::from(io::ErrorKind::InvalidInput));
-		}
+	/// Get the number of lines requested for events.
+	pub fn len(&self) -> usize {
+		self.offset.len()
+	}
+
+	/// Check whether no lines are requested for events.
+	pub fn is_empty(&self) -> bool {
+		self.offset.is_empty()
+	}
+}
+
+/// Represents the writer half of a GpioLineValue obtained with *GpioLineValue::split*.
+pub struct GpioLineWriter {
+	line: Arc<GpioLineValue>,
+}
+
+impl GpioLineWriter {
+	/// Set all the lines to the same value, see *GpioLineValue::set_line_value*.
+	pub fn set_line_value(&self, value: u8) -> io::Result<()> {
+		self.line.set_line_value(value)
+	}
+
+	/// Set the value of a single line, see *GpioLineValue::set_single_line_value*.
+	pub fn set_single_line_value(&self, line: u32, value: u8) -> io::Result<()> {
+		self.line.set_single_line_value(line, value)
+	}
+
+	/// Set the values of a subset of the lines, see *GpioLineValue::set_subset_line_values*.
+	pub fn set_subset_line_values(&self, lines: &[u32], values: &[u8]) -> io::Result<()> {
+		self.line.set_subset_line_values(lines, values)
+	}
+
+	/// Set the values of the lines selected by a mask, see *GpioLineValue::set_bits_masked*.
+	pub fn set_bits_masked(&self, bits: u64, mask: u64) -> io::Result<()> {
+		self.line.set_bits_masked(bits, mask)
+	}
 
-		gpio_handle_request.consumer_label[..label.len()].copy_from_slice(label.as_bytes());
+	/// Get the direction of the lines, see *GpioLineValue::direction*.
+	pub fn direction(&self) -> &LineDirection {
+		self.line.direction()
+	}
+}
+
+/// Represents the reader half of a GpioLineValue obtained with *GpioLineValue::split*.
+pub struct GpioLineReader {
+	line: Arc<GpioLineValue>,
+}
+
+impl GpioLineReader {
+	/// Get the value of the lines, see *GpioLineValue::get_line_value*.
+	pub fn get_line_value(&self) -> io::Result<Vec<u8>> {
+		self.line.get_line_value()
+	}
+
+	/// Get the value of a single line, see *GpioLineValue::get_single_line_value*.
+	pub fn get_single_line_value(&self, line: u32) -> io::Result<u8> {
+		self.line.get_single_line_value(line)
+	}
+
+	/// Get the direction of the lines, see *GpioLineValue::direction*.
+	pub fn direction(&self) -> &LineDirection {
+		self.line.direction()
+	}
+}
+
+/// Represents output lines prepared for repeated writes, obtained with *GpioLineValue::prepare_output*.
+pub struct GpioPreparedOutput<'a> {
+	line: &'a GpioLineValue,
+	data: gpio_ioctl::GpioHandleData,
+}
 
-		unsafe {
-			convert_nix_to_io_result(gpio_ioctl::gpio_get_line_handle(self.fd.as_raw_fd(),&mut gpio_handle_request))?;
+impl GpioPreparedOutput<'_> {
+	/// Set the values of the lines from the bits of *bits*, where bit 0 is the first requested line,
+	/// bit 1 the second requested line and so on. Bits beyond the number of requested lines are ignored.
+	pub fn set_bits(&mut self, bits: u64) -> io::Result<()> {
+		for index in 0..self.line.offset.len() {
+			self.data.values[index] = ((bits >> index) & 1) as u8;
 		}
 
-		Ok(GpioLineValue {
-				parent_chip_name: self.name.clone(),
-				direction: LineDirection::Output,
-				offset: line_offset.clone(),
-				fd: unsafe{File::from_raw_fd(gpio_handle_request.fd)},	})
+		let mut output_values = self.line.output_values.lock().unwrap();
+
+		gpio_ioctl_call("gpio_set_line_values", &self.line.offset, || unsafe { gpio_ioctl::gpio_set_line_values(self.line.fd.as_raw_fd(), &mut self.data) })?;
+		output_values.copy_from_slice(&self.data.values[..self.line.offset.len()]);
+
+		Ok(())
 	}
+}
 
-	/// Request the GPIO chip to configure the lines passed as argument as inputs. Calling this
-	/// operation is a precondition to being able to read the state of the GPIO lines.
-	pub fn request_line_values_input(&self, line_offset: &Vec<u32>, active_low: bool, label: &str) -> io::Result<GpioLineValue> {
-		let mut gpio_handle_request = gpio_ioctl::GpioHandleRequest::default();
-		
-		for index in 0 .. line_offset.len() {
-			gpio_handle_request.line_offsets[index] = line_offset[index];
+/// Represents the mapping between the offsets of the lines of a request and their position, i.e. the index
+/// in the values returned by *get_line_value* and the bit used by *set_bits* and *set_bits_masked*. It is
+/// obtained with *GpioLineValue::line_map* and lets stored or transmitted values be interpreted away from
+/// the request they were read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMap {
+	offset: Vec<u32>,
+}
+
+impl LineMap {
+	/// Get the position of the line with the given offset, or *None* if the line is not part of the request.
+	pub fn bit(&self, offset: u32) -> Option<usize> {
+		self.offset.iter().position(|&x| x == offset)
+	}
+
+	/// Get the offset of the line at the given position, or *None* if the position is beyond the lines of
+	/// the request.
+	pub fn offset(&self, bit: usize) -> Option<u32> {
+		self.offset.get(bit).copied()
+	}
+
+	/// Get the offsets of all the lines, in order of position.
+	pub fn offsets(&self) -> &[u32] {
+		&self.offset
+	}
+}
+
+/// Represents any number of GPIO lines requested together, beyond the limit of 64 lines of a single request.
+/// The lines are held by several underlying requests of up to 64 lines each. Operations on the bank are
+/// executed request by request, so unlike the operations of a GpioLineValue they are not atomic across
+/// all the lines of the bank.
+pub struct GpioLineBank {
+	lines: Vec<GpioLineValue>,
+}
+
+impl GpioLineBank {
+	/// Get the value of all the lines of the bank, in the same order as they were requested.
+	pub fn get_line_value(&self) -> io::Result<Vec<u8>> {
+		let mut values = Vec::new();
+
+		for line in &self.lines {
+			values.extend(line.get_line_value()?);
 		}
 
-		gpio_handle_request.lines = line_offset.len() as u32;
-		
-		gpio_handle_request.flags |= GPIOHANDLE_REQUEST_INPUT;
+		Ok(values)
+	}
 
-		if active_low {
-			gpio_handle_request.flags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
+	/// Set all the lines of the bank to the same value. If one of the underlying requests fails, the
+	/// returned error holds a *PartialSetError* telling which lines were already written.
+	pub fn set_line_value(&self, value: u8) -> io::Result<()> {
+		self.set_each_request(|line, _| line.set_line_value(value))
+	}
+
+	/// Set the value of each line of the bank, in the same order as they were requested. Each value is
+	/// routed to the underlying request holding its line. A number of values which doesn't match the
+	/// number of lines fails with *InvalidInput* before any line is written. If one of the underlying
+	/// requests fails, the returned error holds a *PartialSetError* telling which lines were already written.
+	pub fn set_line_values(&self, values: &[u8]) -> io::Result<()> {
+		if values.len() != self.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Values do not match the number of requested lines"));
 		}
 
-		if label.len() > 32 {
-			return Err(io::Error::from(io::ErrorKind::InvalidInput));
+		let chunks : Vec<&[u8]> = values.chunks(gpio_ioctl::GPIOHANDLES_MAX).collect();
+
+		self.set_each_request(|line, index| line.write_line_values(chunks[index]))
+	}
+
+	fn set_each_request<F: Fn(&GpioLineValue, usize) -> io::Result<()>>(&self, set: F) -> io::Result<()> {
+		let mut written = Vec::new();
+
+		for (index, line) in self.lines.iter().enumerate() {
+			if let Err(source) = set(line, index) {
+				return Err(PartialSetError { written, failed_request: index, source }.into());
+			}
+			written.extend_from_slice(&line.offset);
 		}
 
-		gpio_handle_request.consumer_label[..label.len()].copy_from_slice(label.as_bytes());
+		Ok(())
+	}
+
+	/// Get the offsets of all the lines of the bank.
+	pub fn offset(&self) -> Vec<u32> {
+		self.lines.iter().flat_map(|line| line.offset.iter().copied()).collect()
+	}
+
+	/// Get the underlying requests of the bank.
+	pub fn lines(&self) -> &[GpioLineValue] {
+		&self.lines
+	}
+
+	/// Get the direction of the lines of the bank. All the underlying requests are made with the same
+	/// direction, so this is the direction of every line of the bank.
+	pub fn direction(&self) -> LineDirection {
+		self.lines.first().map_or(LineDirection::Input, |line| *line.direction())
+	}
+
+	/// Get the number of lines of the bank across all the underlying requests.
+	pub fn len(&self) -> usize {
+		self.lines.iter().map(GpioLineValue::len).sum()
+	}
+
+	/// Check whether the bank holds no lines.
+	pub fn is_empty(&self) -> bool {
+		self.lines.iter().all(GpioLineValue::is_empty)
+	}
+}
+
+/// Represents the failure of an operation setting the values of a GpioLineBank on one of its underlying
+/// requests, telling which lines were already written so that the caller can restore a known state. It is
+/// returned as the inner error of an *io::Error* of the same kind as the underlying error and can be
+/// retrieved using *io::Error::get_ref* and *downcast_ref*.
+#[derive(Debug)]
+pub struct PartialSetError {
+	written: Vec<u32>,
+	failed_request: usize,
+	source: io::Error,
+}
+
+impl PartialSetError {
+	/// Get the offsets of the lines whose values were set before the failure.
+	pub fn written(&self) -> &[u32] {
+		&self.written
+	}
+
+	/// Get the index, within the bank, of the underlying request which failed.
+	pub fn failed_request(&self) -> usize {
+		self.failed_request
+	}
+}
+
+impl fmt::Display for PartialSetError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Setting values failed on request {} after writing {} lines: {}", self.failed_request, self.written.len(), self.source)
+	}
+}
+
+impl std::error::Error for PartialSetError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.source)
+	}
+}
+
+impl From<PartialSetError> for io::Error {
+	fn from(error: PartialSetError) -> io::Error {
+		io::Error::new(error.source.kind(), error)
+	}
+}
+
+/// Iterating over a GpioLineEvent blocks waiting for the events of the requested lines. The iteration
+/// never ends by itself, errors are returned as items so the caller decides whether to stop.
+impl Iterator for GpioLineEvent {
+	type Item = io::Result<GpioEvent>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		Some(self.read_event())
+	}
+}
+
+/// Represents the reason of a change in the information of a watched GPIO line. Change identifiers not
+/// known to this crate are returned as *Unknown* with the raw identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoChangeKind {
+	Requested,
+	Released,
+	Reconfigured,
+	Unknown(u32),
+}
 
-		unsafe {
-			convert_nix_to_io_result(gpio_ioctl::gpio_get_line_handle(self.fd.as_raw_fd(), &mut gpio_handle_request))?;
+impl fmt::Display for InfoChangeKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			InfoChangeKind::Requested => write!(f, "Requested"),
+			InfoChangeKind::Released => write!(f, "Released"),
+			InfoChangeKind::Reconfigured => write!(f, "Reconfigured"),
+			InfoChangeKind::Unknown(id) => write!(f, "Unknown change {}", id),
 		}
+	}
+}
 
-		Ok(GpioLineValue{
-				parent_chip_name: self.name.clone(),
-				direction: LineDirection::Input,
-				offset: line_offset.clone(),
-				fd: unsafe{File::from_raw_fd(gpio_handle_request.fd)} })
+/// Represents a change in the information of a GPIO line watched with *GpioChip::watch_line_info*.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioLineInfoChange {
+	offset: u32,
+	kind: InfoChangeKind,
+	timestamp: u64,
+	info: GpioLineInfo,
+}
+
+impl GpioLineInfoChange {
+	/// Get the offset of the line whose information changed.
+	pub fn offset(&self) -> u32 {
+		self.offset
 	}
 
-	/// Get the GPIO chip name.
-	pub fn name(&self) -> &str {
+	/// Get the reason of the change.
+	pub fn kind(&self) -> &InfoChangeKind {
+		&self.kind
+	}
+
+	/// Get the kernel timestamp of the change in nanoseconds, taken from CLOCK_MONOTONIC.
+	pub fn timestamp(&self) -> u64 {
+		self.timestamp
+	}
+
+	/// Get the information of the line after the change.
+	pub fn info(&self) -> &GpioLineInfo {
+		&self.info
+	}
+}
+
+/// Represents the chip information exactly as returned by the kernel, without trimming at the null
+/// terminator or decoding as UTF-8. This is a low-level diagnostic view obtained with
+/// *GpioChip::raw_chip_info*, meant for chips whose name or label look wrong once decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawChipInfo {
+	name: [u8; 32],
+	label: [u8; 32],
+	lines: u32,
+}
+
+impl RawChipInfo {
+	/// Get the raw buffer of the chip name.
+	pub fn name(&self) -> &[u8; 32] {
 		&self.name
 	}
 
-	/// Get the GPIO chip label.
-	pub fn label(&self) -> &str {
+	/// Get the raw buffer of the chip label.
+	pub fn label(&self) -> &[u8; 32] {
 		&self.label
 	}
 
-	/// Get the total number of lines of the GPIO chip.
-	pub fn num_lines(&self) -> &u32 {
-		&self.num_lines
+	/// Get the number of lines reported by the chip.
+	pub fn lines(&self) -> u32 {
+		self.lines
 	}
-}
\ No newline at end of file
+}
+
+/// Represents a watch on the information of all the lines of a GPIO chip, obtained with
+/// *GpioChip::watch_all_lines*. All the changes arrive through the single file descriptor of the chip, so
+/// watching a chip with many lines costs no more than watching one line. The lines are unwatched when this
+/// object is dropped.
+pub struct ChipWatch<'a> {
+	chip: &'a GpioChip,
+	lines: Vec<GpioLineInfo>,
+}
+
+impl ChipWatch<'_> {
+	/// Get the information of all the lines of the chip when the watch started, indexed by line offset.
+	pub fn lines(&self) -> &[GpioLineInfo] {
+		&self.lines
+	}
+
+	/// Wait for the next change in the information of any line of the chip and return it. This function
+	/// blocks until a change is available.
+	pub fn read_change(&self) -> io::Result<GpioLineInfoChange> {
+		self.chip.read_line_info_change()
+	}
+}
+
+impl Iterator for ChipWatch<'_> {
+	type Item = io::Result<GpioLineInfoChange>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		Some(self.read_change())
+	}
+}
+
+impl Drop for ChipWatch<'_> {
+	fn drop(&mut self) {
+		for line_number in 0..self.lines.len() as u32 {
+			let _ = self.chip.unwatch_line_info(&line_number);
+		}
+	}
+}
+
+/// Represents the information of a specific GPIO line. Can only be obtained through the GpioChip interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioLineInfo {
+	flags: LineFlags,
+	direction: LineDirection,
+	active_state: LineActiveState,
+	used: bool,
+	open_drain: bool,
+	open_source: bool,
+	name: String,
+	consumer: String,
+}
+
+/// Represents a set of GPIO lines requested from a GpioChip as either inputs or outputs. The lines
+/// are held until this object is dropped. It is *Send* and *Sync* so it can be shared between threads.
+pub struct GpioLineValue {
+	parent_chip_name: String,
+	consumer: String,
+	direction: LineDirection,
+	output_mode: OutputMode,
+	active_low: bool,
+	readable: bool,
+	offset: Vec<u32>,
+	// Values last written to the lines, used as the base of the helpers which change only some of the
+	// lines instead of reading the values back. Holding the lock while writing keeps those helpers from
+	// losing each other's changes when the request is shared between threads. It is shared with the
+	// blocking tasks of the asynchronous writes, which outlive the future if it is dropped.
+	output_values: Arc<Mutex<Vec<u8>>>,
+	safe_state: Option<Vec<u8>>,
+	fd: File,
+}
+
+/// If a safe state was set with *set_safe_state*, it is written to the lines before they are released.
+/// Errors can not be reported from *drop* so a failure to write the safe state is ignored (and logged
+/// with the log feature).
+impl Drop for GpioLineValue {
+	fn drop(&mut self) {
+		if let Some(safe_state) = &self.safe_state {
+			let _result = self.write_line_values(safe_state);
+
+			#[cfg(feature = "log")]
+			if let Err(e) = &_result {
+				log::warn!("gpio offsets={:?} failed to write safe state on release: {}", self.offset, e);
+			}
+		}
+	}
+}
+
+/// Gives access to the line handle file descriptor, e.g. to issue ioctls not wrapped by this crate. The
+/// file descriptor remains owned by the GpioLineValue: it must not be closed nor left in a state
+/// incompatible with the operations of this crate.
+impl AsRawFd for GpioLineValue {
+	fn as_raw_fd(&self) -> RawFd {
+		self.fd.as_raw_fd()
+	}
+}
+
+impl GpioLineValue {
+	/// Get the value of GPIO lines. The values can only be read if the lines have previously been
+	/// requested as either inputs, using the *request_line_values_input* method, or outputs using 
+	/// the *request_line_values_output*. For outputs requested as open drain or open source, many
+	/// controllers can not read back the line level reliably, check *is_readable* before trusting the result.
+	pub fn get_line_value(&self) -> io::Result<Vec<u8>>{
+		let mut data = gpio_ioctl::GpioHandleData::default();
+
+		gpio_ioctl_call("gpio_get_line_values", &self.offset, || unsafe { gpio_ioctl::gpio_get_line_values(self.fd.as_raw_fd(), &mut data) })?;
+
+		let mut output_data : Vec<u8> = Vec::with_capacity(self.offset.len());
+
+		for index in 0..self.offset.len() {
+			output_data.push(data.values[index]);
+		}
+
+		Ok(output_data)
+	}
+
+	/// Get the value of the GPIO lines together with the time at which they were read. The time is taken
+	/// in user space right after the ioctl returns, so it is less precise than the kernel timestamps of
+	/// events, but it avoids the skew of reading the clock separately.
+	pub fn get_line_value_timed(&self) -> io::Result<(Vec<u8>, SystemTime)> {
+		let values = self.get_line_value()?;
+		Ok((values, SystemTime::now()))
+	}
+
+	/// Check whether any of the requested lines is currently high. A request without lines is never high.
+	pub fn any_high(&self) -> io::Result<bool> {
+		Ok(self.get_line_value()?.iter().any(|&value| value != 0))
+	}
+
+	/// Check whether all the requested lines are currently high. A request without lines is always high.
+	pub fn all_high(&self) -> io::Result<bool> {
+		Ok(self.get_line_value()?.iter().all(|&value| value != 0))
+	}
+
+	/// Get the number of requested lines which are currently high.
+	pub fn count_high(&self) -> io::Result<u32> {
+		Ok(self.get_line_value()?.iter().filter(|&&value| value != 0).count() as u32)
+	}
+
+	/// Read the current values of the lines and compare them with *previous*, a result of an earlier call
+	/// to *get_line_value*. Returns the offset and new value of each line which changed. This allows
+	/// detecting transitions by polling on lines not requested for edge detection.
+	pub fn changed_since(&self, previous: &[u8]) -> io::Result<Vec<(u32, u8)>> {
+		if previous.len() != self.offset.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Previous values do not match the number of requested lines"));
+		}
+
+		let changed = self.get_line_value()?.into_iter()
+			.zip(previous)
+			.zip(&self.offset)
+			.filter(|((value, previous_value), _)| value != *previous_value)
+			.map(|((value, _), offset)| (*offset, value))
+			.collect();
+
+		Ok(changed)
+	}
+
+	/// Set the value of GPIO lines. The value can only be set if the lines have previously been
+	/// requested as outputs using the *request_line_values_output*. All the requested lines, and only
+	/// those, are driven to the same value in a single operation, which makes this function suitable
+	/// for driving every output to a safe state (e.g. all off on shutdown).
+	pub fn set_line_value(&self, value: u8) -> io::Result<()>{
+		self.write_line_values(&vec![value; self.offset.len()])
+	}
+
+	/// Asynchronous version of *set_line_value* for GPIO chips whose set operation can block for a long
+	/// time, such as I2C expanders. The ioctl itself is not asynchronous, it is executed on the blocking
+	/// thread pool of the selected async runtime (*tokio* or *async-std* features) so that it doesn't
+	/// stall the executor.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn set_line_value_async(&self, value: u8) -> io::Result<()> {
+		let fd = self.fd.try_clone()?;
+		let offset = self.offset.clone();
+		let values = vec![value; self.offset.len()];
+		let output_values = self.output_values.clone();
+
+		// The lock is taken by the blocking task so that the write and the update of the written values
+		// stay together even if this future is dropped while the task runs
+		async_spawn_blocking(move || {
+			let mut output_values = output_values.lock().unwrap();
+			write_handle_values(fd.as_raw_fd(), &offset, &values)?;
+			output_values.copy_from_slice(&values);
+			Ok(())
+		}).await
+	}
+
+	/// Get the value of a single line of the request. Fails with *InvalidInput* if the line is not part of the request.
+	pub fn get_single_line_value(&self, line: u32) -> io::Result<u8> {
+		let index = self.line_index(line)?;
+		Ok(self.get_line_value()?[index])
+	}
+
+	/// Set the value of a single line of the request, leaving the other lines untouched. Fails with
+	/// *InvalidInput* if the line is not part of the request. The kernel sets all the lines of a request
+	/// at once so the other lines are written again with the values last written through this request.
+	/// These are used instead of reading the lines back, which for open drain and open source outputs
+	/// would give the level of the bus rather than the driven value.
+	pub fn set_single_line_value(&self, line: u32, value: u8) -> io::Result<()> {
+		let index = self.line_index(line)?;
+
+		self.modify_line_values(|values| values[index] = value)?;
+
+		Ok(())
+	}
+
+	/// Set the values of a subset of the requested lines, leaving the other lines untouched. The *values*
+	/// are given in the same order as *lines*. Fails with *InvalidInput* if any of the lines is not part of
+	/// the request. As with *set_single_line_value*, the other lines keep the values last written.
+	pub fn set_subset_line_values(&self, lines: &[u32], values: &[u8]) -> io::Result<()> {
+		if lines.len() != values.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Values do not match the number of lines of the subset"));
+		}
+
+		let indexes = lines.iter().map(|line| self.line_index(*line)).collect::<io::Result<Vec<usize>>>()?;
+
+		self.modify_line_values(|line_values| {
+			for (index, value) in indexes.iter().zip(values) {
+				line_values[*index] = *value;
+			}
+		})?;
+
+		Ok(())
+	}
+
+	/// Set the values of all the requested lines, in the same order as they were requested, and return the
+	/// values last written before, i.e. the values driven by this request rather than values read back from
+	/// the lines. Other writers of the same request can not slip in between, but the kernel can't report
+	/// whether another process or the hardware changed the lines meanwhile.
+	pub fn swap_line_values(&self, values: &[u8]) -> io::Result<Vec<u8>> {
+		if values.len() != self.offset.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Values do not match the number of requested lines"));
+		}
+
+		self.modify_line_values(|line_values| line_values.copy_from_slice(values))
+	}
+
+	/// Set the values of the lines selected by *mask* from the bits of *bits*, leaving the other lines
+	/// untouched. As with *GpioPreparedOutput::set_bits*, bit 0 is the first requested line, bit 1 the
+	/// second requested line and so on. A mask selecting bits beyond the number of requested lines fails
+	/// with *InvalidInput* instead of being silently cut. As with *set_single_line_value*, the lines
+	/// outside the mask keep the values last written.
+	pub fn set_bits_masked(&self, bits: u64, mask: u64) -> io::Result<()> {
+		if mask & !self.line_mask() != 0 {
+			return Err(Error::new(ErrorKind::InvalidInput,
+				format!("Mask {:#x} selects lines beyond the {} requested lines", mask, self.offset.len())));
+		}
+
+		self.modify_line_values(|values| {
+			for (index, value) in values.iter_mut().enumerate() {
+				if (mask >> index) & 1 == 1 {
+					*value = ((bits >> index) & 1) as u8;
+				}
+			}
+		})?;
+
+		Ok(())
+	}
+
+	/// Drive the lines passed as argument to *value* for *duration* and afterwards restore them to the
+	/// values they had before the pulse. The remaining lines of the request are left untouched. The lines
+	/// must be part of the original output request, otherwise the function fails with *InvalidInput*.
+	/// The pulse timing relies on *thread::sleep* so it is best-effort and subject to the OS scheduling,
+	/// which makes it unsuitable for sub-millisecond precision.
+	pub fn pulse(&self, lines: &[u32], value: u8, duration: Duration) -> io::Result<()> {
+		let indexes = lines.iter().map(|line| self.line_index(*line)).collect::<io::Result<Vec<usize>>>()?;
+
+		let previous_value = self.set_indexes(&indexes, value)?;
+		thread::sleep(duration);
+		self.restore_indexes(&indexes, &previous_value)
+	}
+
+	/// Drive a software PWM on one of the requested lines: for each of the *cycles* periods the line is
+	/// driven high for *duty* times *period* and low for the rest of the period. The remaining lines of
+	/// the request are left untouched. The *duty* must be between 0.0 and 1.0, otherwise the function
+	/// fails with *InvalidInput*. As with *pulse*, the timing relies on *thread::sleep* so it is only
+	/// suitable for slow loads such as dimming an LED, not for servos or precise motor control.
+	pub fn pwm(&self, line: u32, period: Duration, duty: f32, cycles: u32) -> io::Result<()> {
+		let (index, high_time, low_time) = self.pwm_timing(line, period, duty)?;
+
+		for _ in 0..cycles {
+			if !high_time.is_zero() {
+				self.set_indexes(&[index], 1)?;
+				thread::sleep(high_time);
+			}
+			if !low_time.is_zero() {
+				self.set_indexes(&[index], 0)?;
+				thread::sleep(low_time);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Asynchronous version of *pwm* which waits using the sleep of the selected async runtime
+	/// (*tokio* or *async-std* features) instead of blocking the thread. If the returned future is
+	/// dropped the line keeps the level of the last half period that was applied.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn pwm_async(&self, line: u32, period: Duration, duty: f32, cycles: u32) -> io::Result<()> {
+		let (index, high_time, low_time) = self.pwm_timing(line, period, duty)?;
+
+		for _ in 0..cycles {
+			if !high_time.is_zero() {
+				self.set_indexes(&[index], 1)?;
+				async_sleep(high_time).await;
+			}
+			if !low_time.is_zero() {
+				self.set_indexes(&[index], 0)?;
+				async_sleep(low_time).await;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Blink one of the requested lines, e.g. a status LED: the line is driven high for *on* and low for
+	/// *off*, *times* times, and afterwards restored to the value it had before. The remaining lines of the
+	/// request are left untouched. As with *pulse*, the timing relies on *thread::sleep*.
+	pub fn blink(&self, line: u32, on: Duration, off: Duration, times: u32) -> io::Result<()> {
+		let index = self.line_index(line)?;
+		let previous_value = self.output_values.lock().unwrap().clone();
+
+		for _ in 0..times {
+			self.set_indexes(&[index], 1)?;
+			thread::sleep(on);
+			self.set_indexes(&[index], 0)?;
+			thread::sleep(off);
+		}
+
+		self.restore_indexes(&[index], &previous_value)
+	}
+
+	/// Asynchronous version of *blink* which waits using the sleep of the selected async runtime
+	/// (*tokio* or *async-std* features) instead of blocking the thread. If the returned future is
+	/// dropped the line is not restored and keeps the level it was blinking at.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn blink_async(&self, line: u32, on: Duration, off: Duration, times: u32) -> io::Result<()> {
+		let index = self.line_index(line)?;
+		let previous_value = self.output_values.lock().unwrap().clone();
+
+		for _ in 0..times {
+			self.set_indexes(&[index], 1)?;
+			async_sleep(on).await;
+			self.set_indexes(&[index], 0)?;
+			async_sleep(off).await;
+		}
+
+		self.restore_indexes(&[index], &previous_value)
+	}
+
+	// Index of the PWM line in the request and the time spent high and low in each period
+	fn pwm_timing(&self, line: u32, period: Duration, duty: f32) -> io::Result<(usize, Duration, Duration)> {
+		if !(0.0..=1.0).contains(&duty) {
+			return Err(Error::new(ErrorKind::InvalidInput, format!("Duty cycle {} is not between 0.0 and 1.0", duty)));
+		}
+
+		let index = self.line_index(line)?;
+		let high_time = period.mul_f32(duty);
+
+		Ok((index, high_time, period.saturating_sub(high_time)))
+	}
+
+	// Set the lines at the given indexes of the request to value and return the values from before
+	fn set_indexes(&self, indexes: &[usize], value: u8) -> io::Result<Vec<u8>> {
+		self.modify_line_values(|values| {
+			for index in indexes {
+				values[*index] = value;
+			}
+		})
+	}
+
+	// Set the lines at the given indexes of the request back to their value in previous_value, leaving
+	// the other lines as they are now
+	fn restore_indexes(&self, indexes: &[usize], previous_value: &[u8]) -> io::Result<()> {
+		self.modify_line_values(|values| {
+			for index in indexes {
+				values[*index] = previous_value[*index];
+			}
+		})?;
+
+		Ok(())
+	}
+
+	/// Clock out a byte to a shift register such as the 74HC595 using three of the requested output lines:
+	/// *data*, *clock* and *latch*. The latch is driven low, each bit is put on the data line and clocked in
+	/// with a rising edge on the clock line, and the latch is driven high to transfer the byte to the outputs.
+	/// No delays are inserted between the steps, the timing is given by the duration of each ioctl, which
+	/// is well above the setup and hold times of common shift registers but depends on the system load.
+	pub fn shift_out(&self, data: u32, clock: u32, latch: u32, byte: u8, msb_first: bool) -> io::Result<()> {
+		let data_index = self.line_index(data)?;
+		let clock_index = self.line_index(clock)?;
+		let latch_index = self.line_index(latch)?;
+
+		// The lock is held for the whole byte so no other writer of the request interleaves with the clocking
+		let mut output_values = self.output_values.lock().unwrap();
+		let mut write = |change: &dyn Fn(&mut [u8])| -> io::Result<()> {
+			let mut values = output_values.clone();
+			change(&mut values);
+			write_handle_values(self.fd.as_raw_fd(), &self.offset, &values)?;
+			*output_values = values;
+			Ok(())
+		};
+
+		write(&|values| {
+			values[clock_index] = 0;
+			values[latch_index] = 0;
+		})?;
+
+		for bit in 0..8 {
+			let shift = if msb_first { 7 - bit } else { bit };
+
+			write(&|values| values[data_index] = (byte >> shift) & 1)?;
+			write(&|values| values[clock_index] = 1)?;
+			write(&|values| values[clock_index] = 0)?;
+		}
+
+		write(&|values| values[latch_index] = 1)
+	}
+
+	/// Asynchronous version of *pulse* which waits using the sleep of the selected async runtime
+	/// (*tokio* or *async-std* features) instead of blocking the thread. If the returned future is
+	/// dropped while waiting the lines are not restored and keep the pulse value.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn pulse_async(&self, lines: &[u32], value: u8, duration: Duration) -> io::Result<()> {
+		let indexes = lines.iter().map(|line| self.line_index(*line)).collect::<io::Result<Vec<usize>>>()?;
+
+		let previous_value = self.set_indexes(&indexes, value)?;
+		async_sleep(duration).await;
+		self.restore_indexes(&indexes, &previous_value)
+	}
+
+	/// Drive a scripted waveform on the requested lines. Each step sets the values of all the lines,
+	/// in the same order as they were requested, and then waits for the step duration before moving on to
+	/// the next step. The sequence can be cancelled at any point by dropping the returned future, in which
+	/// case the lines keep the values of the last step that was applied.
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	pub async fn sequence_async(&self, steps: &[(Vec<u8>, Duration)]) -> io::Result<()> {
+		if steps.iter().any(|(values, _)| values.len() != self.offset.len()) {
+			return Err(Error::new(ErrorKind::InvalidInput, "Sequence step values do not match the number of requested lines"));
+		}
+
+		for (values, duration) in steps {
+			self.write_line_values(values)?;
+			async_sleep(*duration).await;
+		}
+
+		Ok(())
+	}
+
+	/// Change the requested lines to outputs on the same handle, without releasing them, e.g. for protocols
+	/// where a line alternates between input and output. The *values* are the initial output values of
+	/// the lines, in the same order as they were requested, and are applied together with the new
+	/// configuration to avoid a glitch. Requires Linux 5.5 or later.
+	pub fn reconfigure_as_output(&mut self, output_mode: OutputMode, active_low: bool, values: &[u8]) -> io::Result<()> {
+		if values.len() != self.offset.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Output values do not match the number of requested lines"));
+		}
+
+		let mut gpio_handle_config = gpio_ioctl::GpioHandleConfig {
+			flags: GPIOHANDLE_REQUEST_OUTPUT,
+			..Default::default()
+		};
+
+		match output_mode {
+			OutputMode::OpenDrain => gpio_handle_config.flags |= GPIOHANDLE_REQUEST_OPEN_DRAIN,
+			OutputMode::OpenSource => gpio_handle_config.flags |= GPIOHANDLE_REQUEST_OPEN_SOURCE,
+			_ => (),
+		};
+
+		if active_low {
+			gpio_handle_config.flags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
+		}
+
+		gpio_handle_config.default_values[..values.len()].copy_from_slice(values);
+
+		gpio_ioctl_call("gpio_set_line_config", &self.offset, || unsafe { gpio_ioctl::gpio_set_line_config(self.fd.as_raw_fd(), &mut gpio_handle_config) })?;
+
+		*self.output_values.lock().unwrap() = values.to_vec();
+		self.direction = LineDirection::Output;
+		self.output_mode = output_mode;
+		self.active_low = active_low;
+		self.readable = matches!(output_mode, OutputMode::None);
+
+		Ok(())
+	}
+
+	/// Change the requested lines to inputs on the same handle, without releasing them.
+	/// Requires Linux 5.5 or later.
+	pub fn reconfigure_as_input(&mut self, active_low: bool) -> io::Result<()> {
+		let mut gpio_handle_config = gpio_ioctl::GpioHandleConfig {
+			flags: GPIOHANDLE_REQUEST_INPUT,
+			..Default::default()
+		};
+
+		if active_low {
+			gpio_handle_config.flags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
+		}
+
+		gpio_ioctl_call("gpio_set_line_config", &self.offset, || unsafe { gpio_ioctl::gpio_set_line_config(self.fd.as_raw_fd(), &mut gpio_handle_config) })?;
+
+		self.direction = LineDirection::Input;
+		self.output_mode = OutputMode::None;
+		self.active_low = active_low;
+		self.readable = true;
+
+		Ok(())
+	}
+
+	/// Set the values, in the same order as the lines were requested, which are written to the lines when
+	/// this object is dropped, including during a panic unwind. This guarantees a defined level for
+	/// outputs such as motor enables or relays, instead of whatever the kernel does when the lines are released.
+	pub fn set_safe_state(&mut self, values: Vec<u8>) -> io::Result<()> {
+		if values.len() != self.offset.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Safe state values do not match the number of requested lines"));
+		}
+
+		self.safe_state = Some(values);
+
+		Ok(())
+	}
+
+	/// Split the lines into a writer half and a reader half which can be moved to different threads or
+	/// tasks, e.g. one driving the outputs while the other samples them. Both halves share the same line
+	/// handle, the lines are released when both are dropped. The kernel serializes the operations on the
+	/// handle so each read returns the values before or after a write, never a mix of both.
+	pub fn split(self) -> (GpioLineWriter, GpioLineReader) {
+		let line = Arc::new(self);
+		(GpioLineWriter { line: line.clone() }, GpioLineReader { line })
+	}
+
+	/// Prepare the lines for setting their values repeatedly, e.g. for bit-banging. The returned object
+	/// keeps the buffer passed to the kernel between writes instead of building a new one for each write.
+	pub fn prepare_output(&self) -> GpioPreparedOutput<'_> {
+		GpioPreparedOutput {
+			line: self,
+			data: gpio_ioctl::GpioHandleData::default(),
+		}
+	}
+
+	fn line_index(&self, line: u32) -> io::Result<usize> {
+		self.offset.iter().position(|&x| x == line)
+			.ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Line {} is not part of the request", line)))
+	}
+
+	fn write_line_values(&self, values: &[u8]) -> io::Result<()> {
+		let mut output_values = self.output_values.lock().unwrap();
+
+		write_handle_values(self.fd.as_raw_fd(), &self.offset, values)?;
+		output_values.copy_from_slice(values);
+
+		Ok(())
+	}
+
+	// Change some of the values last written with *change* and write them, all while holding the lock of
+	// the written values, and return the values from before the change.
+	fn modify_line_values<F: FnOnce(&mut [u8])>(&self, change: F) -> io::Result<Vec<u8>> {
+		let mut output_values = self.output_values.lock().unwrap();
+		let mut values = output_values.clone();
+
+		change(&mut values);
+		write_handle_values(self.fd.as_raw_fd(), &self.offset, &values)?;
+
+		Ok(std::mem::replace(&mut *output_values, values))
+	}
+
+	pub fn parent_chip_name(&self) -> &str {
+		&self.parent_chip_name
+	}
+
+	/// Get the consumer label the lines were requested with, as reported by *get_line_info* for them.
+	pub fn consumer(&self) -> &str {
+		&self.consumer
+	}
+
+	/// Get the offsets of the requested lines, in the same order as they were requested.
+	pub fn offset(&self) -> &[u32] {
+		&self.offset
+	}
+
+	/// Get the mapping between the offsets of the requested lines and their position in the values.
+	pub fn line_map(&self) -> LineMap {
+		LineMap { offset: self.offset.clone() }
+	}
+
+	/// Get the mask of the bits used for the requested lines by *set_bits* and *set_bits_masked*, with one
+	/// bit set per requested line starting at bit 0. A mask can be limited to the lines held by the request
+	/// with *mask & line.line_mask()*.
+	pub fn line_mask(&self) -> u64 {
+		u64::MAX.checked_shr(64 - self.offset.len() as u32).unwrap_or(0)
+	}
+
+	/// Get the number of lines held by the request.
+	pub fn len(&self) -> usize {
+		self.offset.len()
+	}
+
+	/// Check whether the request holds no lines.
+	pub fn is_empty(&self) -> bool {
+		self.offset.is_empty()
+	}
+
+	/// Get the direction the lines are currently configured with, as given by the original request or
+	/// by the last *reconfigure_as_output* or *reconfigure_as_input*. No system call is made.
+	pub fn direction(&self) -> &LineDirection {
+		&self.direction
+	}
+
+	/// Get the output mode the lines are currently configured with, *None* for inputs and push-pull
+	/// outputs. Like *direction*, it follows the reconfigurations of the request.
+	pub fn output_mode(&self) -> OutputMode {
+		self.output_mode
+	}
+
+	/// Check whether the lines are currently configured as active low. Like *direction*, it follows the
+	/// reconfigurations of the request.
+	pub fn is_active_low(&self) -> bool {
+		self.active_low
+	}
+
+	/// Get the physical level of the lines, in the same order as they were requested. The values returned
+	/// by *get_line_value* are logical values, inverted by the kernel for active low lines, while these
+	/// are the electrical levels of the lines with the active low configuration undone.
+	pub fn get_physical_line_value(&self) -> io::Result<Vec<u8>> {
+		self.get_line_value_as(Polarity::Physical)
+	}
+
+	/// Get the value of the lines with the given interpretation, in the same order as they were
+	/// requested. *Polarity::Logical* gives the same values as *get_line_value* and *Polarity::Physical*
+	/// the same values as *get_physical_line_value*.
+	pub fn get_line_value_as(&self, polarity: Polarity) -> io::Result<Vec<u8>> {
+		let values = self.get_line_value()?;
+
+		match polarity {
+			Polarity::Physical if self.active_low => Ok(values.into_iter().map(|value| (value == 0) as u8).collect()),
+			_ => Ok(values),
+		}
+	}
+
+	/// Check whether the values returned by *get_line_value* are meaningful for this request. This is the
+	/// case for inputs and push-pull outputs but not for open drain or open source outputs, whose
+	/// read back value depends on the controller.
+	pub fn is_readable(&self) -> bool {
+		self.readable
+	}
+}
+
+impl fmt::Display for GpioLineInfo {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "\t {}", self.direction)?;
+		if self.used {
+			write!(f, "\t Used")?;
+		}
+		else {
+			write!(f, "\t Unused")?;
+		}
+		if self.consumer.is_empty() {
+			write!(f, "\t Unnamed")?;
+		}
+		else {
+			write!(f,"\t {}", self.consumer)?;
+		}
+		write!(f,"\t {}", self.active_state())?;
+		if self.open_drain {
+			write!(f,"\t Open drain")?;
+		}
+		else if self.open_source {
+			write!(f,"\t Open source")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl GpioLineInfo {
+	fn from_raw(gpio_line_info: &gpio_ioctl::GpioLineInfo) -> GpioLineInfo {
+		let flags = LineFlags::from_bits_truncate(gpio_line_info.flags);
+
+		let direction = if flags.contains(LineFlags::OUTPUT) {
+			LineDirection::Output
+		} else {
+			LineDirection::Input
+		};
+
+		let active_state = if flags.contains(LineFlags::ACTIVE_LOW) {
+			LineActiveState::ActiveLow
+		} else {
+			LineActiveState::ActiveHigh
+		};
+
+		let used = flags.contains(LineFlags::USED);
+		let open_drain = flags.contains(LineFlags::OPEN_DRAIN);
+		let open_source = flags.contains(LineFlags::OPEN_SOURCE);
+		let name = string_from_c_buffer(&gpio_line_info.name);
+		let consumer = string_from_c_buffer(&gpio_line_info.consumer);
+		
+		GpioLineInfo{
+			flags,
+			direction,
+			active_state,
+			used,
+			open_drain,
+			open_source,
+			name,
+			consumer,
+		}
+	}
+
+	/// Get the complete set of flags reported by the kernel for the line.
+	pub fn flags(&self) -> LineFlags {
+		self.flags
+	}
+
+	pub fn direction(&self) -> &LineDirection {
+		&self.direction
+	}
+
+	pub fn active_state(&self) -> &LineActiveState {
+		&self.active_state
+	}
+
+	/// Check whether the line is configured as input.
+	pub fn is_input(&self) -> bool {
+		self.direction == LineDirection::Input
+	}
+
+	/// Check whether the line is configured as output.
+	pub fn is_output(&self) -> bool {
+		self.direction == LineDirection::Output
+	}
+
+	pub fn is_used(&self) -> &bool {
+		&self.used
+	}
+
+	pub fn is_open_drain(&self) -> &bool {
+		&self.open_drain
+	}
+
+	pub fn is_open_source(&self) -> &bool {
+		&self.open_source
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn consumer(&self) -> &str {
+		&self.consumer
+	}
+}
+
+impl GpioChip {
+
+	/// Create a new GPIO chip interface.
+	pub fn new(path: &dyn AsRef<Path>) -> io::Result<GpioChip> {
+		let dev_file = OpenOptions::new().read(true).write(true).open(path)
+			.map_err(error_context(&format!("Opening GPIO chip device {}", path.as_ref().display())))?;
+
+		GpioChip::is_gpiochip_cdev(path)
+			.map_err(error_context("Checking GPIO chip device against sysfs"))?;
+
+		GpioChip::from_file(dev_file, Some(path.as_ref().to_path_buf()), true)
+	}
+
+	/// Create a new GPIO chip interface without the sysfs cross-check done by *new*, so sysfs is not read
+	/// at all, e.g. for systems without sysfs mounted. Only the character device type of the opened file
+	/// is checked before the chip info ioctl, so a character device which is not a GPIO chip is only caught
+	/// by the ioctl failing. Use it only on trusted paths.
+	pub fn new_unchecked(path: &dyn AsRef<Path>) -> io::Result<GpioChip> {
+		let dev_file = OpenOptions::new().read(true).write(true).open(path)
+			.map_err(error_context(&format!("Opening GPIO chip device {}", path.as_ref().display())))?;
+
+		if !dev_file.metadata()?.file_type().is_char_device() {
+			return Err(Error::new(ErrorKind::InvalidInput,"File is not character device"));
+		}
+
+		GpioChip::from_file(dev_file, Some(path.as_ref().to_path_buf()), false)
+	}
+
+	/// Create a new GPIO chip interface from an already opened GPIO chip file descriptor, e.g. one
+	/// passed by a privileged process to a sandboxed one. The chip takes ownership of the file descriptor.
+	/// Since there is no path available, only the character device type is checked and the sysfs
+	/// cross-check done by *new* is skipped.
+	pub fn from_fd(fd: OwnedFd) -> io::Result<GpioChip> {
+		let dev_file = File::from(fd);
+
+		if !dev_file.metadata()?.file_type().is_char_device() {
+			return Err(Error::new(ErrorKind::InvalidInput,"File is not character device"));
+		}
+
+		GpioChip::from_file(dev_file, None, false)
+	}
+
+	/// Check whether the kernel supports the v2 GPIO character device ABI, added in Linux 5.10, for the
+	/// chip at *path*. This is a best-effort probe: the v2 line info of line 0 is requested and a rejection
+	/// of the ioctl itself is taken as missing support. This crate only uses the v1 ABI, so the result is
+	/// only informative, e.g. for tools which need v2 features through other means.
+	pub fn supports_v2(path: &dyn AsRef<Path>) -> io::Result<bool> {
+		let chip = GpioChip::new(path)?;
+		let mut gpio_line_info = gpio_ioctl::GpioV2LineInfo::default();
+
+		match gpio_ioctl_call("gpio_v2_get_line_info", &[0], || unsafe { gpio_ioctl::gpio_v2_get_line_info(chip.fd.as_raw_fd(), &mut gpio_line_info) }) {
+			Ok(_) => Ok(true),
+			// Kernels without v2 reject the unknown ioctl with EINVAL, other drivers with ENOTTY
+			Err(ref e) if matches!(e.raw_os_error(), Some(nix::libc::ENOTTY) | Some(nix::libc::EINVAL)) => Ok(false),
+			Err(e) => Err(e),
+		}
+	}
+
+	fn from_file(dev_file: File, path: Option<PathBuf>, sysfs_checked: bool) -> io::Result<GpioChip> {
+		let mut gpio_chip_info = gpio_ioctl::GpioChipInfo::default();
+
+		gpio_ioctl_call("gpio_get_chip_info", &[], || unsafe { gpio_ioctl::gpio_get_chip_info(dev_file.as_raw_fd(), &mut gpio_chip_info) })
+			.map_err(error_context("Reading GPIO chip info"))?;
+
+		let name = string_from_c_buffer(&gpio_chip_info.name);
+		let rdev = dev_file.metadata()?.rdev();
+
+		Ok (GpioChip{
+				sysfs_path: sysfs_gpio_devices().join(&name),
+				name,
+				label: string_from_c_buffer(&gpio_chip_info.label),
+				num_lines: gpio_chip_info.lines,
+				device_number: (nix::sys::stat::major(rdev) as u32, nix::sys::stat::minor(rdev) as u32),
+				line_info_cache: Mutex::new(HashMap::new()),
+				path,
+				sysfs_checked,
+				fd: dev_file,})
+	}
+
+	fn is_gpiochip_cdev(path: &dyn AsRef<Path>) -> io::Result<bool>{
+		/*rv = lstat(path, &statbuf);*/
+		let file_metadata = symlink_metadata(path)?; 
+
+		/*if (!S_ISCHR(statbuf.st_mode)) */
+		if !file_metadata.file_type().is_char_device() { 
+			return Err(Error::new(ErrorKind::InvalidInput,"File is not character device"));
+		}
+
+		/*basename(pathcpy);*/
+		let basename = path.as_ref().file_name()
+			.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Path has no file name"))?;
+
+		let sysfs = sysfs_gpio_devices().join(basename).join("dev");
+
+		/*if (access(sysfsp, R_OK) != 0)*/
+		if !sysfs.is_file() /*I check if it is a file instead of read access done in libgpiod */ {
+			return  Err(Error::new(ErrorKind::InvalidInput,"Matching GPIO in sys not found"));
+		}
+
+		let sysfs_rdev = std::fs::read(&sysfs)
+			.map_err(error_context(&format!("Reading {}", sysfs.display())))?;
+		let sysfs_rdev = String::from_utf8_lossy(&sysfs_rdev);
+
+		let rdev = file_metadata.rdev();
+		let file_rdev = format!("{}:{}", nix::sys::stat::major(rdev), nix::sys::stat::minor(rdev));
+
+		if sysfs_rdev.trim_end() != file_rdev {
+			return Err(Error::new(ErrorKind::InvalidInput,
+				format!("Device number {} does not match {:?} of {}", file_rdev, sysfs_rdev.trim_end(), sysfs.display())));
+		}
+
+		Ok(true)
+	}
+
+	/// Request the chip information from the kernel again and return it undecoded. This is a low-level
+	/// diagnostic accessor, the decoded values are available with *name*, *label* and *num_lines*.
+	pub fn raw_chip_info(&self) -> io::Result<RawChipInfo> {
+		let mut gpio_chip_info = gpio_ioctl::GpioChipInfo::default();
+
+		gpio_ioctl_call("gpio_get_chip_info", &[], || unsafe { gpio_ioctl::gpio_get_chip_info(self.fd.as_raw_fd(), &mut gpio_chip_info) })?;
+
+		Ok(RawChipInfo {
+			name: gpio_chip_info.name,
+			label: gpio_chip_info.label,
+			lines: gpio_chip_info.lines,
+		})
+	}
+
+	/// Request the info of a specific GPIO line.
+	pub fn get_line_info(&self, line_number: &u32) -> io::Result<GpioLineInfo>{
+		let mut gpio_line_info = gpio_ioctl::GpioLineInfo {
+			line_offset: *line_number,
+			..Default::default()
+		};
+
+		gpio_ioctl_call("gpio_get_line_info", &[*line_number], || unsafe { gpio_ioctl::gpio_get_line_info(self.fd.as_raw_fd(), &mut gpio_line_info) })?;
+
+		Ok(GpioLineInfo::from_raw(&gpio_line_info))
+	}
+
+	/// Start watching a GPIO line for changes in its information, i.e. when it is requested, released or
+	/// reconfigured, and return its current information. The changes are read with *read_line_info_change*.
+	/// Requires Linux 5.7 or later.
+	pub fn watch_line_info(&self, line_number: &u32) -> io::Result<GpioLineInfo> {
+		let mut gpio_line_info = gpio_ioctl::GpioLineInfo {
+			line_offset: *line_number,
+			..Default::default()
+		};
+
+		gpio_ioctl_call("gpio_watch_line_info", &[*line_number], || unsafe { gpio_ioctl::gpio_watch_line_info(self.fd.as_raw_fd(), &mut gpio_line_info) })?;
+
+		Ok(GpioLineInfo::from_raw(&gpio_line_info))
+	}
+
+	/// Start watching all the lines of the GPIO chip for changes in their information, e.g. for a live
+	/// view of the chip. The current information of the lines is available from the returned watch,
+	/// which yields the changes of any line. If a line can not be watched, the lines already watched are
+	/// unwatched again before the error is returned. Requires Linux 5.7 or later.
+	pub fn watch_all_lines(&self) -> io::Result<ChipWatch<'_>> {
+		let mut watch = ChipWatch {
+			chip: self,
+			lines: Vec::with_capacity(self.num_lines as usize),
+		};
+
+		for line_number in 0..self.num_lines {
+			let info = self.watch_line_info(&line_number)?;
+			watch.lines.push(info);
+		}
+
+		Ok(watch)
+	}
+
+	/// Stop watching a GPIO line previously watched with *watch_line_info*.
+	pub fn unwatch_line_info(&self, line_number: &u32) -> io::Result<()> {
+		let mut line_offset = *line_number;
+
+		gpio_ioctl_call("gpio_unwatch_line_info", &[*line_number], || unsafe { gpio_ioctl::gpio_unwatch_line_info(self.fd.as_raw_fd(), &mut line_offset) })?;
+
+		Ok(())
+	}
+
+	/// Wait for the next change in the information of any of the watched lines and return it. This
+	/// function blocks until a change is available.
+	pub fn read_line_info_change(&self) -> io::Result<GpioLineInfoChange> {
+		let mut data = [0u8; gpio_ioctl::GPIO_LINE_INFO_CHANGED_SIZE];
+
+		read_record(&self.fd, &mut data)?;
+
+		let mut gpio_line_info = gpio_ioctl::GpioLineInfo::default();
+		let mut word = [0u8; 4];
+		word.copy_from_slice(&data[0..4]);
+		gpio_line_info.line_offset = u32::from_ne_bytes(word);
+		word.copy_from_slice(&data[4..8]);
+		gpio_line_info.flags = u32::from_ne_bytes(word);
+		gpio_line_info.name.copy_from_slice(&data[8..40]);
+		gpio_line_info.consumer.copy_from_slice(&data[40..72]);
+
+		let mut timestamp = [0u8; 8];
+		timestamp.copy_from_slice(&data[72..80]);
+		word.copy_from_slice(&data[80..84]);
+
+		let kind = match u32::from_ne_bytes(word) {
+			GPIOLINE_CHANGED_REQUESTED => InfoChangeKind::Requested,
+			GPIOLINE_CHANGED_RELEASED => InfoChangeKind::Released,
+			GPIOLINE_CHANGED_CONFIG => InfoChangeKind::Reconfigured,
+			id => InfoChangeKind::Unknown(id),
+		};
+
+		let info = GpioLineInfo::from_raw(&gpio_line_info);
+
+		if let Some(cached_info) = self.line_info_cache.lock().unwrap().get_mut(&gpio_line_info.line_offset) {
+			*cached_info = info.clone();
+		}
+
+		Ok(GpioLineInfoChange {
+			offset: gpio_line_info.line_offset,
+			kind,
+			timestamp: u64::from_ne_bytes(timestamp),
+			info,
+		})
+	}
+
+	/// Get the info of a specific GPIO line from a cache kept by the chip, requesting it from the kernel only
+	/// the first time. Changes read with *read_line_info_change* update the cache, but without a watch on the
+	/// line the cached info goes stale when the line is requested, released or reconfigured, including
+	/// by other processes. Use *invalidate_line_info* and *invalidate_all_line_info* to refresh it.
+	pub fn get_line_info_cached(&self, line_number: &u32) -> io::Result<GpioLineInfo> {
+		if let Some(info) = self.line_info_cache.lock().unwrap().get(line_number) {
+			return Ok(info.clone());
+		}
+
+		let info = self.get_line_info(line_number)?;
+		self.line_info_cache.lock().unwrap().insert(*line_number, info.clone());
+
+		Ok(info)
+	}
+
+	/// Remove the info of a specific GPIO line from the cache used by *get_line_info_cached*.
+	pub fn invalidate_line_info(&self, line_number: &u32) {
+		self.line_info_cache.lock().unwrap().remove(line_number);
+	}
+
+	/// Remove the info of all the GPIO lines from the cache used by *get_line_info_cached*.
+	pub fn invalidate_all_line_info(&self) {
+		self.line_info_cache.lock().unwrap().clear();
+	}
+
+	/// Request the info of several GPIO lines, returned in the order of *line_numbers*. The kernel has no
+	/// call for several lines, so this issues one ioctl per line as *get_line_info* does.
+	pub fn get_line_infos(&self, line_numbers: &[u32]) -> io::Result<Vec<GpioLineInfo>> {
+		let mut line_infos = Vec::with_capacity(line_numbers.len());
+		let mut gpio_line_info = gpio_ioctl::GpioLineInfo::default();
+
+		for line_number in line_numbers {
+			gpio_line_info.line_offset = *line_number;
+
+			gpio_ioctl_call("gpio_get_line_info", &[*line_number], || unsafe { gpio_ioctl::gpio_get_line_info(self.fd.as_raw_fd(), &mut gpio_line_info) })?;
+
+			line_infos.push(GpioLineInfo::from_raw(&gpio_line_info));
+		}
+
+		Ok(line_infos)
+	}
+
+	/// Check whether a specific GPIO line is currently free to be requested. This only queries the line
+	/// info so, unlike attempting a request, it doesn't disturb the line.
+	pub fn is_line_available(&self, line_number: &u32) -> io::Result<bool> {
+		Ok(!*self.get_line_info(line_number)?.is_used())
+	}
+
+	/// Get the offsets of all the GPIO lines of the chip which are currently free to be requested.
+	pub fn available_lines(&self) -> io::Result<Vec<u32>> {
+		let mut available_lines = Vec::new();
+
+		for line_number in 0..self.num_lines {
+			if self.is_line_available(&line_number)? {
+				available_lines.push(line_number);
+			}
+		}
+
+		Ok(available_lines)
+	}
+
+	/// Get the offsets of all the GPIO lines of the chip which are currently used together with their
+	/// consumer label. Lines for which the info can not be retrieved are skipped.
+	pub fn used_lines(&self) -> io::Result<Vec<(u32, String)>> {
+		let used_lines = (0..self.num_lines)
+			.filter_map(|line_number| self.get_line_info(&line_number).ok().map(|info| (line_number, info)))
+			.filter(|(_, info)| *info.is_used())
+			.map(|(line_number, info)| (line_number, info.consumer))
+			.collect();
+
+		Ok(used_lines)
+	}
+
+	/// Get the offsets of the used GPIO lines of the chip grouped by their consumer label. Lines used
+	/// without a consumer label, typically by the kernel itself, are grouped under *"<kernel>"*.
+	pub fn lines_by_consumer(&self) -> io::Result<HashMap<String, Vec<u32>>> {
+		let line_numbers : Vec<u32> = (0..self.num_lines).collect();
+		let mut lines_by_consumer : HashMap<String, Vec<u32>> = HashMap::new();
+
+		for (line_number, info) in line_numbers.iter().zip(self.get_line_infos(&line_numbers)?) {
+			if *info.is_used() {
+				let consumer = if info.consumer.is_empty() { String::from("<kernel>") } else { info.consumer };
+				lines_by_consumer.entry(consumer).or_default().push(*line_number);
+			}
+		}
+
+		Ok(lines_by_consumer)
+	}
+
+	/// Find the offset of the GPIO line with the given name. Returns *None* if no line of the chip has that name.
+	pub fn find_line_by_name(&self, name: &str) -> io::Result<Option<u32>> {
+		for line_number in 0..self.num_lines {
+			if self.get_line_info(&line_number)?.name() == name {
+				return Ok(Some(line_number));
+			}
+		}
+
+		Ok(None)
+	}
+
+	/// Get an identifier of a line which stays valid across reboots and kernel upgrades, for configuration
+	/// files: the chip label and the line name, e.g. *pinctrl-bcm2835:GPIO17*. Unnamed lines fall back to
+	/// the chip label and the offset, e.g. *pinctrl-bcm2835:17*, which is only as stable as the offset.
+	pub fn stable_key(&self, line_number: &u32) -> io::Result<String> {
+		let info = self.get_line_info(line_number)?;
+
+		if info.name().is_empty() {
+			Ok(format!("{}:{}", self.label, line_number))
+		} else {
+			Ok(format!("{}:{}", self.label, info.name()))
+		}
+	}
+
+	/// Find the offset of the line identified by a key returned by *stable_key*. Returns *None* if the key
+	/// belongs to another chip, has an empty line part or no line of this chip matches it. Line names take
+	/// precedence over offsets, so a line named like a number is found by name.
+	pub fn find_by_stable_key(&self, key: &str) -> io::Result<Option<u32>> {
+		let line = match key.strip_prefix(self.label.as_str()).and_then(|key| key.strip_prefix(':')) {
+			Some(line) if !line.is_empty() => line,
+			_ => return Ok(None),
+		};
+
+		if let Some(line_number) = self.find_line_by_name(line)? {
+			return Ok(Some(line_number));
+		}
+
+		Ok(line.parse::<u32>().ok().filter(|&line_number| line_number < self.num_lines))
+	}
+
+	/// Convert the line selectors of a line specification into offsets of this chip. Lines selected by
+	/// name are searched with *find_line_by_name* and fail with *NotFound* if the chip has no such line.
+	pub fn resolve(&self, selectors: &[LineSelector]) -> io::Result<Vec<u32>> {
+		selectors.iter()
+			.map(|selector| match selector {
+				LineSelector::Offset(offset) => Ok(*offset),
+				LineSelector::Name(name) => self.find_line_by_name(name)?
+					.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Line \"{}\" not found on {}", name, self.name))),
+			})
+			.collect()
+	}
+
+	/// Request the GPIO chip to configure the lines passed as argument as outputs. Calling this
+	/// operation is a precondition to being able to set the state of the GPIO lines. All the lines
+	/// passed in one request must share the output mode and the active state. The state of lines configured
+	/// as outputs can also be read using the *get_line_value* method.
+	pub fn request_line_values_output(&self, line_offset: &[u32], output_mode: OutputMode, active_low: bool, label: &str) -> io::Result<GpioLineValue> {
+		self.request_output(line_offset, output_mode, active_low, &[], label)
+	}
+
+	/// Request the lines passed as argument with the direction given by *config*: if it has initial values
+	/// the lines are requested as outputs already driven to those values, otherwise they are requested as
+	/// inputs. The direction of the returned request can be checked with *GpioLineValue::direction*.
+	pub fn request(&self, line_offset: &[u32], config: &RequestConfig) -> io::Result<GpioLineValue> {
+		match &config.initial_values {
+			Some(values) => {
+				if values.len() != line_offset.len() {
+					return Err(Error::new(ErrorKind::InvalidInput, "Initial values do not match the number of requested lines"));
+				}
+				if config.bias != LineBias::None {
+					return Err(Error::new(ErrorKind::InvalidInput, "A bias can only be given for inputs, i.e. without initial values"));
+				}
+				self.request_output(line_offset, config.output_mode, config.active_low, values, &config.label)
+			},
+			None => {
+				if config.output_mode != OutputMode::None {
+					return Err(Error::new(ErrorKind::InvalidInput, "An output mode can only be given for outputs, i.e. with initial values"));
+				}
+				self.request_input(line_offset, config.active_low, config.bias, &config.label)
+			},
+		}
+	}
+
+	// Request the lines as outputs driven to default_values, or low for the lines without a default value
+	fn request_output(&self, line_offset: &[u32], output_mode: OutputMode, active_low: bool, default_values: &[u8], label: &str) -> io::Result<GpioLineValue> {
+		self.request_output_with(&self.fd, line_offset, output_mode, active_low, default_values, label)
+	}
+
+	// Same as request_output with the kernel requests passed to the given ioctls
+	fn request_output_with(&self, ioctl: &dyn LineRequestIoctl, line_offset: &[u32], output_mode: OutputMode, active_low: bool, default_values: &[u8], label: &str) -> io::Result<GpioLineValue> {
+		check_line_count(line_offset)?;
+		self.check_line_offsets(line_offset)?;
+
+		let mut gpio_handle_request = gpio_ioctl::GpioHandleRequest {
+			lines: line_offset.len() as u32,
+			flags: GPIOHANDLE_REQUEST_OUTPUT,
+			..Default::default()
+		};
+
+		gpio_handle_request.line_offsets[..line_offset.len()].copy_from_slice(line_offset);
+		gpio_handle_request.default_values[..default_values.len()].copy_from_slice(default_values);
+		
+		match output_mode {
+			OutputMode::OpenDrain => gpio_handle_request.flags |= GPIOHANDLE_REQUEST_OPEN_DRAIN,
+			OutputMode::OpenSource => gpio_handle_request.flags |= GPIOHANDLE_REQUEST_OPEN_SOURCE,
+			_ => (),
+		};
+
+		let readable = matches!(output_mode, OutputMode::None);
+		let output_values = gpio_handle_request.default_values[..line_offset.len()].to_vec();
+
+		if active_low {
+			gpio_handle_request.flags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
+		}
+
+		gpio_handle_request.consumer_label = consumer_label(label)?;
+
+		ioctl.get_line_handle(&mut gpio_handle_request)
+			.map_err(error_context(&self.request_error_stage(line_offset)))?;
+
+		Ok(GpioLineValue {
+				parent_chip_name: self.name.clone(),
+				consumer: label.to_string(),
+				direction: LineDirection::Output,
+				output_mode,
+				active_low,
+				readable,
+				offset: line_offset.to_vec(),
+				output_values: Arc::new(Mutex::new(output_values)),
+				safe_state: None,
+				fd: unsafe{File::from_raw_fd(gpio_handle_request.fd)},	})
+	}
+
+	/// Request the GPIO chip to configure the lines passed as argument as inputs. Calling this
+	/// operation is a precondition to being able to read the state of the GPIO lines.
+	pub fn request_line_values_input(&self, line_offset: &[u32], active_low: bool, label: &str) -> io::Result<GpioLineValue> {
+		self.request_input(line_offset, active_low, LineBias::None, label)
+	}
+
+	/// Request the lines passed as argument as inputs with the given bias, e.g. a switch read with a
+	/// pull-up. This is a plain value request: no edge detection is set up and the kernel creates no event
+	/// buffers for it, so the returned *GpioLineValue* has no *read_event* at all. Lines whose edges are
+	/// needed must be requested with *request_line_events* or *request_button* instead.
+	pub fn request_line_values_input_with_bias(&self, line_offset: &[u32], active_low: bool, bias: LineBias, label: &str) -> io::Result<GpioLineValue> {
+		self.request_input(line_offset, active_low, bias, label)
+	}
+
+	/// Request the lines passed as argument as inputs after checking that they have the expected names,
+	/// which catches a program addressing lines by offset on the wrong board before it touches them. The
+	/// *expected_names* are given in the same order as the lines, *None* skips the check for a line. A
+	/// mismatch fails with a *GpioError::LineNameMismatch* and no line is requested.
+	pub fn request_line_values_input_checked(&self, line_offset: &[u32], expected_names: &[Option<&str>], active_low: bool, label: &str) -> io::Result<GpioLineValue> {
+		if expected_names.len() != line_offset.len() {
+			return Err(Error::new(ErrorKind::InvalidInput, "Expected names do not match the number of requested lines"));
+		}
+
+		self.check_line_offsets(line_offset)?;
+
+		for (offset, expected) in line_offset.iter().zip(expected_names) {
+			if let Some(expected) = expected {
+				let info = self.get_line_info(offset)?;
+				if info.name() != *expected {
+					return Err(GpioError::LineNameMismatch {
+						offset: *offset,
+						expected: expected.to_string(),
+						found: info.name().to_string(),
+					}.into());
+				}
+			}
+		}
+
+		self.request_line_values_input(line_offset, active_low, label)
+	}
+
+	/// Read the value of a single line once, in the way of the *gpioget* tool: the line is requested as
+	/// input with the given bias, read and released right away. If the line is held by another consumer
+	/// the kernel rejects the request and the returned error tells so.
+	pub fn read_line_once(&self, line_number: &u32, active_low: bool, bias: LineBias, label: &str) -> io::Result<u8> {
+		let line = self.request_input(&[*line_number], active_low, bias, label)
+			.map_err(|e| match ContextError::raw_os_error(&e) {
+				Some(nix::libc::EBUSY) => error_context(&format!("Line {} is held by another consumer", line_number))(e),
+				_ => e,
+			})?;
+
+		Ok(line.get_line_value()?[0])
+	}
+
+	/// Try to read the value of a line for diagnostics, returning *None* instead of an error when the line
+	/// is held by another consumer. The kernel doesn't allow reading the value of a line held by someone
+	/// else, only its info through *get_line_info*. A free line is briefly requested as input, which turns
+	/// a line left driven as output by a previous consumer into an input.
+	pub fn peek_line(&self, line_number: &u32, label: &str) -> io::Result<Option<u8>> {
+		match self.request_input(&[*line_number], false, LineBias::None, label) {
+			Ok(line) => Ok(Some(line.get_line_value()?[0])),
+			Err(ref e) if ContextError::raw_os_error(e) == Some(nix::libc::EBUSY) => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Drive a single line to *value* once, in the way of the *gpioset* tool: the line is requested as
+	/// output, set, held for *hold* and released. The hold time is needed because the kernel stops driving
+	/// the line when it is released, so the value is only guaranteed while the request is alive and may
+	/// revert right after this function returns. To keep a line driven, hold the *GpioLineValue* returned
+	/// by *request_line_values_output* instead.
+	pub fn write_line_once(&self, line_number: &u32, value: u8, hold: Duration, output_mode: OutputMode, label: &str) -> io::Result<()> {
+		// The value is given as the initial value of the request so the line never glitches low first
+		let _line = self.request_output(&[*line_number], output_mode, false, &[value], label)?;
+
+		thread::sleep(hold);
+
+		Ok(())
+	}
+
+	fn request_input(&self, line_offset: &[u32], active_low: bool, bias: LineBias, label: &str) -> io::Result<GpioLineValue> {
+		self.request_input_with(&self.fd, line_offset, active_low, bias, label)
+	}
+
+	// Same as request_input with the kernel requests passed to the given ioctls
+	fn request_input_with(&self, ioctl: &dyn LineRequestIoctl, line_offset: &[u32], active_low: bool, bias: LineBias, label: &str) -> io::Result<GpioLineValue> {
+		check_line_count(line_offset)?;
+		self.check_line_offsets(line_offset)?;
+
+		let mut gpio_handle_request = gpio_ioctl::GpioHandleRequest {
+			lines: line_offset.len() as u32,
+			flags: GPIOHANDLE_REQUEST_INPUT | bias.request_flags(),
+			..Default::default()
+		};
+
+		gpio_handle_request.line_offsets[..line_offset.len()].copy_from_slice(line_offset);
+
+		if active_low {
+			gpio_handle_request.flags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
+		}
+
+		gpio_handle_request.consumer_label = consumer_label(label)?;
+
+		ioctl.get_line_handle(&mut gpio_handle_request)
+			.map_err(error_context(&self.request_error_stage(line_offset)))?;
+
+		Ok(GpioLineValue{
+				parent_chip_name: self.name.clone(),
+				consumer: label.to_string(),
+				direction: LineDirection::Input,
+				output_mode: OutputMode::None,
+				active_low,
+				readable: true,
+				offset: line_offset.to_vec(),
+				output_values: Arc::new(Mutex::new(vec![0; line_offset.len()])),
+				safe_state: None,
+				fd: unsafe{File::from_raw_fd(gpio_handle_request.fd)} })
+	}
+
+	/// Request any number of lines as outputs. The lines are split in as many requests of at most 64 lines
+	/// as needed, which are then accessed together through the returned line bank.
+	pub fn request_line_bank_output(&self, line_offset: &[u32], output_mode: OutputMode, active_low: bool, label: &str) -> io::Result<GpioLineBank> {
+		self.check_line_offsets(line_offset)?;
+
+		let lines = line_offset.chunks(gpio_ioctl::GPIOHANDLES_MAX)
+			.map(|chunk| self.request_line_values_output(chunk, output_mode, active_low, label))
+			.collect::<io::Result<_>>()?;
+
+		Ok(GpioLineBank { lines })
+	}
+
+	/// Request any number of lines as inputs. The lines are split in as many requests of at most 64 lines
+	/// as needed, which are then accessed together through the returned line bank.
+	pub fn request_line_bank_input(&self, line_offset: &[u32], active_low: bool, label: &str) -> io::Result<GpioLineBank> {
+		self.check_line_offsets(line_offset)?;
+
+		let lines = line_offset.chunks(gpio_ioctl::GPIOHANDLES_MAX)
+			.map(|chunk| self.request_line_values_input(chunk, active_low, label))
+			.collect::<io::Result<_>>()?;
+
+		Ok(GpioLineBank { lines })
+	}
+
+	/// Read the value of all the lines of the chip which are currently free, keyed by line offset. The
+	/// lines are requested as active high inputs only for the time of the read. Chips with more lines
+	/// than a single request can hold are read in several requests through a line bank, so no line
+	/// beyond the first 64 is left out.
+	pub fn snapshot_inputs(&self, label: &str) -> io::Result<HashMap<u32, bool>> {
+		let line_offset = self.available_lines()?;
+		if line_offset.is_empty() {
+			return Ok(HashMap::new());
+		}
+
+		let values = self.request_line_bank_input(&line_offset, false, label)?.get_line_value()?;
+
+		Ok(line_offset.into_iter().zip(values).map(|(offset, value)| (offset, value != 0)).collect())
+	}
+
+	/// Request the lines passed as argument as inputs with the given edge detection and bias and return them
+	/// ready to read events from, either with *read_event* or by iterating over the returned object. This is
+	/// the same as *request_line_events* with active high lines and a bias, e.g. for several push buttons.
+	pub fn monitor(&self, line_offset: &[u32], edge_detection: EdgeDetection, bias: LineBias, label: &str) -> io::Result<GpioLineEvent> {
+		self.request_events(line_offset, edge_detection, false, bias, label)
+	}
+
+	/// Request several independent groups of lines at once, e.g. the outputs and inputs of a board, and
+	/// return their handles in the same order as the requests. The batch is all-or-nothing: if any of the
+	/// requests fails, the lines already acquired by the previous requests of the batch are released
+	/// before the error is returned.
+	pub fn request_batch(&self, requests: Vec<LineRequest>) -> io::Result<Vec<GpioLineValue>> {
+		self.request_batch_with(&self.fd, &requests)
+	}
+
+	// Same as request_batch with the kernel requests passed to the given ioctls
+	fn request_batch_with(&self, ioctl: &dyn LineRequestIoctl, requests: &[LineRequest]) -> io::Result<Vec<GpioLineValue>> {
+		// Dropping the handles collected so far on error is what releases the lines
+		requests.iter()
+			.map(|request| self.request_one_with(ioctl, request))
+			.collect()
+	}
+
+	/// Make a request, retrying it according to *policy* while the kernel reports the lines as busy
+	/// because another consumer holds them, e.g. a service which releases them shortly after start-up.
+	/// Any other error, or the busy error of the last attempt, is returned right away.
+	pub fn request_with_retry(&self, request: &LineRequest, policy: &RetryPolicy) -> io::Result<GpioLineValue> {
+		let mut attempt = 1;
+
+		loop {
+			match self.request_one(request) {
+				Err(ref e) if ContextError::raw_os_error(e) == Some(nix::libc::EBUSY) && attempt < policy.max_attempts => {
+					thread::sleep(policy.delay(attempt));
+					attempt += 1;
+				},
+				result => return result,
+			}
+		}
+	}
+
+	fn request_one(&self, request: &LineRequest) -> io::Result<GpioLineValue> {
+		self.request_one_with(&self.fd, request)
+	}
+
+	fn request_one_with(&self, ioctl: &dyn LineRequestIoctl, request: &LineRequest) -> io::Result<GpioLineValue> {
+		match request {
+			LineRequest::Input{line_offset, active_low, label} =>
+				self.request_input_with(ioctl, line_offset, *active_low, LineBias::None, label),
+			LineRequest::Output{line_offset, output_mode, active_low, label} =>
+				self.request_output_with(ioctl, line_offset, *output_mode, *active_low, &[], label),
+		}
+	}
+
+	/// Request the GPIO chip to configure the lines passed as argument as inputs with edge detection.
+	/// Calling this operation is a precondition to being able to read events from the GPIO lines.
+	pub fn request_line_events(&self, line_offset: &[u32], edge_detection: EdgeDetection, active_low: bool, label: &str) -> io::Result<GpioLineEvent> {
+		self.request_events(line_offset, edge_detection, active_low, LineBias::None, label)
+	}
+
+	/// Request a line connected to a push button as input with the bias and edge detection needed to
+	/// read its presses as events, e.g. a pull up with falling edge detection for a button which shorts
+	/// the line to ground. Debouncing of the edges is only offered by the GPIO chardev v2 interface, which
+	/// is not used by this crate, so bouncing contacts generate several events per press.
+	pub fn request_button(&self, line_number: &u32, edge_detection: EdgeDetection, bias: LineBias, label: &str) -> io::Result<GpioLineEvent> {
+		self.request_events(&[*line_number], edge_detection, false, bias, label)
+	}
+
+	fn request_events(&self, line_offset: &[u32], edge_detection: EdgeDetection, active_low: bool, bias: LineBias, label: &str) -> io::Result<GpioLineEvent> {
+		self.request_events_with(&self.fd, line_offset, edge_detection, active_low, bias, label)
+	}
+
+	// Same as request_events with the kernel requests passed to the given ioctls
+	fn request_events_with(&self, ioctl: &dyn LineRequestIoctl, line_offset: &[u32], edge_detection: EdgeDetection, active_low: bool, bias: LineBias, label: &str) -> io::Result<GpioLineEvent> {
+		self.check_line_offsets(line_offset)?;
+
+		let consumer_label = consumer_label(label)?;
+
+		let mut fd = Vec::with_capacity(line_offset.len());
+
+		for offset in line_offset {
+			let mut gpio_event_request = gpio_ioctl::GpioEventRequest {
+				lineoffset: *offset,
+				handleflags: GPIOHANDLE_REQUEST_INPUT | bias.request_flags(),
+				..Default::default()
+			};
+
+			gpio_event_request.eventflags = match edge_detection {
+				EdgeDetection::RisingEdge => GPIOEVENT_REQUEST_RISING_EDGE,
+				EdgeDetection::FallingEdge => GPIOEVENT_REQUEST_FALLING_EDGE,
+				EdgeDetection::BothEdges => GPIOEVENT_REQUEST_BOTH_EDGES,
+			};
+
+			if active_low {
+				gpio_event_request.handleflags |= GPIOHANDLE_REQUEST_ACTIVE_LOW;
+			}
+
+			gpio_event_request.consumer_label = consumer_label;
+
+			ioctl.get_line_event(&mut gpio_event_request)
+				.map_err(error_context(&self.request_error_stage(&[*offset])))?;
+
+			fd.push(unsafe{File::from_raw_fd(gpio_event_request.fd)});
+		}
+
+		Ok(GpioLineEvent {
+				parent_chip_name: self.name.clone(),
+				consumer: label.to_string(),
+				offset: line_offset.to_vec(),
+				fd,
+				events_read: 0,
+				nonblocking: false, })
+	}
+
+	// Describe a line request for error messages, naming the chip device when it is known
+	fn request_error_stage(&self, line_offset: &[u32]) -> String {
+		match &self.path {
+			Some(path) => format!("Requesting lines {:?} on {}", line_offset, path.display()),
+			None => format!("Requesting lines {:?} on {}", line_offset, self.name),
+		}
+	}
+
+	fn check_line_offsets(&self, line_offset: &[u32]) -> io::Result<()> {
+		if line_offset.is_empty() {
+			return Err(GpioError::NoLines.into());
+		}
+
+		match line_offset.iter().find(|&&offset| offset >= self.num_lines) {
+			Some(offset) => Err(GpioError::InvalidOffset { offset: *offset, num_lines: self.num_lines }.into()),
+			None => Ok(()),
+		}
+	}
+
+	/// Get the GPIO chip name.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Get the GPIO chip label.
+	pub fn label(&self) -> &str {
+		&self.label
+	}
+
+	/// Get the total number of lines of the GPIO chip.
+	pub fn num_lines(&self) -> &u32 {
+		&self.num_lines
+	}
+
+	/// Get the device number of the GPIO chip character device as a (major, minor) pair.
+	pub fn device_number(&self) -> (u32, u32) {
+		self.device_number
+	}
+
+	/// Get the path the GPIO chip was opened from, e.g. */dev/gpiochip0*. Chips created with *from_fd*
+	/// have no path.
+	pub fn path(&self) -> Option<&Path> {
+		self.path.as_deref()
+	}
+
+	/// Open the GPIO chip again from its path, e.g. to get an independent file descriptor or to recover
+	/// after the device was unbound and bound again. The chip is opened the same way as it was first opened,
+	/// so a chip created with *new_unchecked* is reopened without the sysfs cross-check. Fails with
+	/// *NotFound* for chips created with *from_fd*.
+	pub fn reopen(&self) -> io::Result<GpioChip> {
+		match &self.path {
+			Some(path) if self.sysfs_checked => GpioChip::new(path),
+			Some(path) => GpioChip::new_unchecked(path),
+			None => Err(Error::new(ErrorKind::NotFound, "GPIO chip was not opened from a path")),
+		}
+	}
+
+	/// Get the path of the GPIO chip device in sysfs, e.g. */sys/bus/gpio/devices/gpiochip0*.
+	pub fn sysfs_path(&self) -> &Path {
+		&self.sysfs_path
+	}
+
+	/// Read an attribute of the GPIO chip device in sysfs, e.g. *base* or *ngpio*. The trailing line
+	/// feed written by the kernel is removed. An attribute which doesn't exist, like *base* on newer
+	/// kernels, returns an error of kind *NotFound*. Only attributes of the chip device itself can be read,
+	/// names which contain */* or *..* fail with *InvalidInput*.
+	pub fn sysfs_attr(&self, attr: &str) -> io::Result<String> {
+		if attr.is_empty() || attr.contains('/') || attr.contains("..") {
+			return Err(Error::new(ErrorKind::InvalidInput, format!("Invalid sysfs attribute name {:?}", attr)));
+		}
+
+		let value = std::fs::read_to_string(self.sysfs_path.join(attr))?;
+		Ok(value.trim_end_matches('\n').to_string())
+	}
+
+	/// Get the number of the first GPIO of the chip in the legacy sysfs numbering.
+	pub fn gpio_base(&self) -> io::Result<u32> {
+		self.sysfs_attr("base")?.trim().parse::<u32>()
+			.map_err(|e| Error::new(ErrorKind::InvalidData, e))
+	}
+
+	/// Get the name of the kernel driver of the GPIO controller, e.g. *pinctrl-bcm2835*. The GPIO chip
+	/// device in sysfs is a child of the controller device, which links to its driver.
+	pub fn driver_name(&self) -> io::Result<String> {
+		let chip_device = std::fs::canonicalize(&self.sysfs_path)?;
+		let controller = chip_device.parent()
+			.ok_or_else(|| Error::new(ErrorKind::NotFound, "GPIO chip device has no parent in sysfs"))?;
+		let driver = std::fs::read_link(controller.join("driver"))?;
+		match driver.file_name() {
+			Some(name) => Ok(name.to_string_lossy().into_owned()),
+			None => Err(Error::new(ErrorKind::NotFound, "Driver link has no name")),
+		}
+	}
+}
+// Lines of a MultiChip hosted by the same chip: the chip index and the offsets and names of the lines
+type ChipLineGroup<'a> = (usize, Vec<u32>, Vec<&'a str>);
+
+/// Represents a group of GPIO chips which are addressed together, e.g. the SoC controller and an I2C
+/// expander of the same board. Lines are looked up by name across all the chips so the application
+/// doesn't need to know which controller hosts a line.
+pub struct MultiChip {
+	chips: Vec<GpioChip>,
+}
+
+impl MultiChip {
+	/// Create a new group from already opened GPIO chips. The chip index used by the other methods is
+	/// the position of the chip in this vector.
+	pub fn new(chips: Vec<GpioChip>) -> MultiChip {
+		MultiChip { chips }
+	}
+
+	/// Get the GPIO chips of the group.
+	pub fn chips(&self) -> &[GpioChip] {
+		&self.chips
+	}
+
+	/// Find the GPIO line with the given name on any of the chips and return the chip index and line offset.
+	/// The chips are searched in order so the first chip with a line of that name is returned.
+	pub fn find_line_by_name(&self, name: &str) -> io::Result<Option<(usize, u32)>> {
+		for (chip_index, chip) in self.chips.iter().enumerate() {
+			if let Some(offset) = chip.find_line_by_name(name)? {
+				return Ok(Some((chip_index, offset)));
+			}
+		}
+
+		Ok(None)
+	}
+
+	/// Request the lines with the given names as inputs. One request is made for each chip hosting any
+	/// of the lines and the handles are returned in chip order.
+	pub fn request_line_values_input(&self, names: &[&str], active_low: bool, label: &str) -> io::Result<Vec<GpioLineValue>> {
+		self.group_lines_by_chip(names)?.iter()
+			.map(|(chip_index, line_offset, _)| self.chips[*chip_index].request_line_values_input(line_offset, active_low, label))
+			.collect()
+	}
+
+	/// Request the lines with the given names as outputs. One request is made for each chip hosting any
+	/// of the lines and the handles are returned in chip order.
+	pub fn request_line_values_output(&self, names: &[&str], output_mode: OutputMode, active_low: bool, label: &str) -> io::Result<Vec<GpioLineValue>> {
+		self.group_lines_by_chip(names)?.iter()
+			.map(|(chip_index, line_offset, _)| self.chips[*chip_index].request_line_values_output(line_offset, output_mode, active_low, label))
+			.collect()
+	}
+
+	/// Request the lines with the given names for events, across all the chips hosting them, and return
+	/// an object reading the events of all of them tagged with the name of the line.
+	pub fn monitor_by_name(&self, names: &[&str], edge_detection: EdgeDetection, label: &str) -> io::Result<GpioNamedLineEvent> {
+		let mut lines = Vec::new();
+		let mut line_names = HashMap::new();
+
+		for (chip_index, line_offset, chip_line_names) in self.group_lines_by_chip(names)? {
+			for (offset, name) in line_offset.iter().zip(chip_line_names) {
+				line_names.insert((lines.len(), *offset), name.to_string());
+			}
+			lines.push(self.chips[chip_index].monitor(&line_offset, edge_detection, LineBias::None, label)?);
+		}
+
+		Ok(GpioNamedLineEvent { lines, line_names })
+	}
+
+	fn group_lines_by_chip<'a>(&self, names: &[&'a str]) -> io::Result<Vec<ChipLineGroup<'a>>> {
+		let mut groups : Vec<ChipLineGroup<'a>> = Vec::new();
+
+		for name in names {
+			let (chip_index, offset) = self.find_line_by_name(name)?
+				.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Line \"{}\" not found", name)))?;
+
+			match groups.iter_mut().find(|(index, _, _)| *index == chip_index) {
+				Some((_, line_offset, line_names)) => {
+					line_offset.push(offset);
+					line_names.push(name);
+				},
+				None => groups.push((chip_index, vec![offset], vec![name])),
+			}
+		}
+
+		groups.sort_by_key(|(chip_index, _, _)| *chip_index);
+
+		Ok(groups)
+	}
+}
+
+/// Represents lines requested for events by name across several GPIO chips with *MultiChip::monitor_by_name*.
+/// The events of all the lines are read together and tagged with the name of the line which generated them.
+pub struct GpioNamedLineEvent {
+	lines: Vec<GpioLineEvent>,
+	line_names: HashMap<(usize, u32), String>,
+}
+
+impl GpioNamedLineEvent {
+	/// Wait for the next event on any of the requested lines and return it together with the line name.
+	/// This function blocks until an event is available.
+	pub fn read_event(&mut self) -> io::Result<(String, GpioEvent)> {
+		let fd_index : Vec<(usize, usize)> = self.lines.iter().enumerate()
+			.flat_map(|(line_index, line)| (0..line.fd.len()).map(move |index| (line_index, index)))
+			.collect();
+
+		let mut poll_fd : Vec<nix::poll::PollFd> = fd_index.iter()
+			.map(|&(line_index, index)| nix::poll::PollFd::new(self.lines[line_index].fd[index].as_raw_fd(), nix::poll::PollFlags::POLLIN))
+			.collect();
+
+		retry_on_interrupt(|| nix::poll::poll(&mut poll_fd, -1))?;
+
+		let position = poll_fd.iter()
+			.position(poll_fd_readable)
+			.ok_or_else(|| Error::other("Poll returned without any event available"))?;
+
+		let (line_index, index) = fd_index[position];
+		let event = self.lines[line_index].read_event_from(index)?;
+		let name = self.line_names.get(&(line_index, event.offset())).cloned().unwrap_or_default();
+
+		Ok((name, event))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	// The directory overrides are process wide, so the tests changing them must not run concurrently
+	static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+	// Create an empty directory for the fixtures of a test
+	fn fixture_dir(name: &str) -> PathBuf {
+		static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+		let dir = env::temp_dir().join(format!("libgpiod-{}-{}-{}", name, std::process::id(), COUNTER.fetch_add(1, Ordering::SeqCst)));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+
+	// Create a sysfs fixture root where the device "null" has the given dev attribute
+	fn sysfs_fixture(name: &str, dev: &[u8]) -> PathBuf {
+		let root = fixture_dir(name);
+		let device = root.join("bus/gpio/devices/null");
+		fs::create_dir_all(&device).unwrap();
+		fs::write(device.join("dev"), dev).unwrap();
+		root
+	}
+
+	fn null_device_number() -> String {
+		let rdev = symlink_metadata("/dev/null").unwrap().rdev();
+		format!("{}:{}\n", nix::sys::stat::major(rdev), nix::sys::stat::minor(rdev))
+	}
+
+	// Create a chip backed by /dev/null for the tests which don't issue ioctls
+	fn fake_chip(sysfs_path: PathBuf) -> GpioChip {
+		GpioChip {
+			name: String::from("gpiochip0"),
+			label: String::from("fake"),
+			num_lines: 8,
+			device_number: (1, 3),
+			sysfs_path,
+			line_info_cache: Mutex::new(HashMap::new()),
+			path: None,
+			sysfs_checked: false,
+			fd: File::open("/dev/null").unwrap(),
+		}
+	}
+
+	fn context_of(error: &io::Error) -> &str {
+		error.get_ref().and_then(|inner| inner.downcast_ref::<ContextError>()).unwrap().context()
+	}
+
+	#[test]
+	fn new_fails_opening_a_missing_device() {
+		let error = GpioChip::new(&"/dev/libgpiod-missing-chip").err().unwrap();
+
+		assert_eq!(error.kind(), ErrorKind::NotFound);
+		assert!(context_of(&error).starts_with("Opening GPIO chip device"));
+		assert_eq!(ContextError::raw_os_error(&error), Some(nix::libc::ENOENT));
+	}
+
+	#[test]
+	fn cdev_check_rejects_regular_files() {
+		let dir = fixture_dir("regular");
+		let path = dir.join("gpiochip0");
+		fs::write(&path, b"").unwrap();
+
+		let error = GpioChip::is_gpiochip_cdev(&path).err().unwrap();
+		assert_eq!(error.kind(), ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn cdev_check_against_sysfs() {
+		let _lock = ENV_LOCK.lock().unwrap();
+		let device_number = null_device_number();
+
+		let root = fixture_dir("missing");
+		env::set_var("GPIOD_SYSFS_ROOT", &root);
+		assert_eq!(GpioChip::is_gpiochip_cdev(&"/dev/null").err().unwrap().kind(), ErrorKind::InvalidInput);
+
+		let root = sysfs_fixture("match", device_number.as_bytes());
+		env::set_var("GPIOD_SYSFS_ROOT", &root);
+		assert!(GpioChip::is_gpiochip_cdev(&"/dev/null").unwrap());
+
+		// A device number differing only in its last digit must not match
+		let root = sysfs_fixture("mismatch", format!("{}9\n", device_number.trim_end()).as_bytes());
+		env::set_var("GPIOD_SYSFS_ROOT", &root);
+		assert_eq!(GpioChip::is_gpiochip_cdev(&"/dev/null").err().unwrap().kind(), ErrorKind::InvalidInput);
+
+		for content in [&b"\n"[..], &b""[..], &[0xff, 0xfe, b'\n'][..]] {
+			let root = sysfs_fixture("garbage", content);
+			env::set_var("GPIOD_SYSFS_ROOT", &root);
+			assert_eq!(GpioChip::is_gpiochip_cdev(&"/dev/null").err().unwrap().kind(), ErrorKind::InvalidInput);
+		}
+
+		env::remove_var("GPIOD_SYSFS_ROOT");
+	}
+
+	#[test]
+	fn new_reports_the_failing_stage() {
+		let _lock = ENV_LOCK.lock().unwrap();
+
+		let root = fixture_dir("stage-sysfs");
+		env::set_var("GPIOD_SYSFS_ROOT", &root);
+		let error = GpioChip::new(&"/dev/null").err().unwrap();
+		assert_eq!(context_of(&error), "Checking GPIO chip device against sysfs");
+
+		// /dev/null passes the sysfs check of the fixture but rejects the chip info ioctl
+		let root = sysfs_fixture("stage-ioctl", null_device_number().as_bytes());
+		env::set_var("GPIOD_SYSFS_ROOT", &root);
+		let error = GpioChip::new(&"/dev/null").err().unwrap();
+		assert_eq!(context_of(&error), "Reading GPIO chip info");
+		assert_eq!(ContextError::raw_os_error(&error), Some(nix::libc::ENOTTY));
+
+		env::remove_var("GPIOD_SYSFS_ROOT");
+	}
+
+	#[test]
+	fn sysfs_attributes_of_the_chip() {
+		let root = fixture_dir("sysfs-attr");
+		let controller = root.join("devices/platform/gpio-controller");
+		fs::create_dir_all(controller.join("gpiochip0")).unwrap();
+		fs::create_dir_all(root.join("bus/platform/drivers/fake-gpio")).unwrap();
+		fs::create_dir_all(root.join("bus/gpio/devices")).unwrap();
+		std::os::unix::fs::symlink("../../../bus/platform/drivers/fake-gpio", controller.join("driver")).unwrap();
+		std::os::unix::fs::symlink("../../../devices/platform/gpio-controller/gpiochip0", root.join("bus/gpio/devices/gpiochip0")).unwrap();
+		fs::write(controller.join("gpiochip0/base"), b"512\n").unwrap();
+		fs::write(root.join("secret"), b"secret\n").unwrap();
+
+		let chip = fake_chip(root.join("bus/gpio/devices/gpiochip0"));
+
+		assert_eq!(chip.sysfs_attr("base").unwrap(), "512");
+		assert_eq!(chip.gpio_base().unwrap(), 512);
+		assert_eq!(chip.sysfs_attr("ngpio").err().unwrap().kind(), ErrorKind::NotFound);
+		assert_eq!(chip.driver_name().unwrap(), "fake-gpio");
+
+		for attr in ["../../../../secret", "..", "", "subsystem/uevent"] {
+			assert_eq!(chip.sysfs_attr(attr).err().unwrap().kind(), ErrorKind::InvalidInput);
+		}
+	}
+
+	#[test]
+	fn parse_line_spec_offsets_ranges_and_names() {
+		let (chip, lines) = parse_line_spec("gpiochip0:5,7-9, GPIO3").unwrap();
+
+		assert_eq!(chip, ChipRef::Name(String::from("gpiochip0")));
+		assert_eq!(lines, vec![
+			LineSelector::Offset(5),
+			LineSelector::Offset(7),
+			LineSelector::Offset(8),
+			LineSelector::Offset(9),
+			LineSelector::Name(String::from("GPIO3")),
+		]);
+
+		assert_eq!(parse_line_spec("0:1").unwrap(), (ChipRef::Number(0), vec![LineSelector::Offset(1)]));
+		assert_eq!(parse_line_spec("/dev/gpiochip1:4-4").unwrap(),
+			(ChipRef::Path(PathBuf::from("/dev/gpiochip1")), vec![LineSelector::Offset(4)]));
+	}
+
+	#[test]
+	fn parse_line_spec_rejects_invalid_specs() {
+		for spec in ["", "gpiochip0", ":5", "gpiochip0:", "gpiochip0:5,", "gpiochip0:5,,6", "gpiochip0:9-7",
+			"gpiochip0:0-4294967295"] {
+			assert_eq!(parse_line_spec(spec).err().unwrap().kind(), ErrorKind::InvalidInput, "{}", spec);
+		}
+	}
+
+	#[test]
+	fn request_rejects_options_of_the_other_direction() {
+		let chip = fake_chip(PathBuf::from("/sys/bus/gpio/devices/gpiochip0"));
+
+		let input_with_output_mode = RequestConfig { output_mode: OutputMode::OpenDrain, ..Default::default() };
+		assert_eq!(chip.request(&[0], &input_with_output_mode).err().unwrap().kind(), ErrorKind::InvalidInput);
+
+		let output_with_bias = RequestConfig { initial_values: Some(vec![1]), bias: LineBias::PullUp, ..Default::default() };
+		assert_eq!(chip.request(&[0], &output_with_bias).err().unwrap().kind(), ErrorKind::InvalidInput);
+	}
+
+	fn pipe_files() -> (File, File) {
+		let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+		unsafe { (File::from_raw_fd(read_fd), File::from_raw_fd(write_fd)) }
+	}
+
+	#[test]
+	fn read_record_completes_short_reads() {
+		let (reader, mut writer) = pipe_files();
+		let mut record = [0u8; gpio_ioctl::GPIO_EVENT_DATA_SIZE];
+
+		let writing = thread::spawn(move || {
+			writer.write_all(&[1, 2, 3]).unwrap();
+			thread::sleep(Duration::from_millis(20));
+			writer.write_all(&[4; gpio_ioctl::GPIO_EVENT_DATA_SIZE - 3]).unwrap();
+		});
+
+		read_record(&reader, &mut record).unwrap();
+		writing.join().unwrap();
+		assert_eq!(record[..4], [1, 2, 3, 4]);
+		assert!(record[3..].iter().all(|&byte| byte == 4));
+	}
+
+	#[test]
+	fn read_record_tells_end_of_file_from_a_record_cut_short() {
+		let (reader, writer) = pipe_files();
+		drop(writer);
+		let error = read_record(&reader, &mut [0u8; gpio_ioctl::GPIO_EVENT_DATA_SIZE]).err().unwrap();
+		assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(error.to_string(), "GPIO file descriptor reached end of file");
+
+		let (reader, mut writer) = pipe_files();
+		writer.write_all(&[0; 5]).unwrap();
+		drop(writer);
+		let error = read_record(&reader, &mut [0u8; gpio_ioctl::GPIO_EVENT_DATA_SIZE]).err().unwrap();
+		assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(error.to_string(),
+			format!("GPIO record cut short after 5 of {} bytes", gpio_ioctl::GPIO_EVENT_DATA_SIZE));
+	}
+
+	#[test]
+	fn event_data_size_matches_the_kernel_layout() {
+		assert_eq!(gpio_ioctl::GPIO_EVENT_DATA_SIZE,
+			if std::mem::align_of::<u64>() == 8 { 16 } else { 12 });
+	}
+
+	#[test]
+	fn retry_delay_doubles_up_to_the_cap() {
+		let policy = RetryPolicy::default();
+
+		assert_eq!(policy.delay(0), Duration::from_millis(10));
+		assert_eq!(policy.delay(1), Duration::from_millis(10));
+		assert_eq!(policy.delay(2), Duration::from_millis(20));
+		assert_eq!(policy.delay(4), Duration::from_millis(80));
+		assert_eq!(policy.delay(7), Duration::from_millis(640));
+		assert_eq!(policy.delay(8), Duration::from_secs(1));
+		assert_eq!(policy.delay(9), Duration::from_secs(1));
+	}
+
+	#[test]
+	fn retry_delay_saturates_at_large_attempts() {
+		let policy = RetryPolicy { max_attempts: u32::MAX, backoff: Duration::from_secs(u64::MAX / 2), max_backoff: Duration::MAX };
+
+		for attempt in [32, 33, 64, u32::MAX] {
+			assert_eq!(policy.delay(attempt), Duration::MAX, "{}", attempt);
+		}
+		assert_eq!(policy.delay(2), Duration::from_secs(u64::MAX / 2 * 2));
+		assert_eq!(policy.delay(3), Duration::MAX);
+	}
+
+	fn event(offset: u32, edge: LineEdge, timestamp: u64) -> GpioEvent {
+		GpioEvent { offset, edge, timestamp }
+	}
+
+	#[test]
+	fn events_sort_by_timestamp_then_offset() {
+		let mut events = vec![
+			event(3, LineEdge::Rising, 200),
+			event(1, LineEdge::Falling, 300),
+			event(5, LineEdge::Falling, 100),
+			event(2, LineEdge::Rising, 200),
+			event(0, LineEdge::Rising, 300),
+		];
+
+		events.sort_by(GpioEvent::cmp_order);
+
+		assert_eq!(events, vec![
+			event(5, LineEdge::Falling, 100),
+			event(2, LineEdge::Rising, 200),
+			event(3, LineEdge::Rising, 200),
+			event(0, LineEdge::Rising, 300),
+			event(1, LineEdge::Falling, 300),
+		]);
+	}
+
+	#[test]
+	fn precedes_breaks_timestamp_ties_by_offset() {
+		let earlier = event(7, LineEdge::Rising, 100);
+		let tie_low = event(1, LineEdge::Falling, 200);
+		let tie_high = event(2, LineEdge::Rising, 200);
+
+		assert!(earlier.precedes(&tie_low));
+		assert!(!tie_low.precedes(&earlier));
+		assert!(tie_low.precedes(&tie_high));
+		assert!(!tie_high.precedes(&tie_low));
+		assert!(!tie_low.precedes(&tie_low));
+		assert_eq!(tie_low.cmp_order(&event(1, LineEdge::Rising, 200)), std::cmp::Ordering::Equal);
+	}
+
+	fn gpio_error_of(error: &io::Error) -> &GpioError {
+		assert_eq!(error.kind(), ErrorKind::InvalidInput);
+		error.get_ref().and_then(|inner| inner.downcast_ref::<GpioError>()).unwrap()
+	}
+
+	#[test]
+	fn consumer_label_rejects_labels_too_long() {
+		let error = consumer_label(&"x".repeat(40)).err().unwrap();
+		assert_eq!(gpio_error_of(&error), &GpioError::LabelTooLong { len: 40, max: 31 });
+
+		let label = consumer_label(&"x".repeat(31)).unwrap();
+		assert!(label[..31].iter().all(|&byte| byte == b'x'));
+		assert_eq!(label[31], 0);
+	}
+
+	#[test]
+	fn check_line_count_rejects_too_many_lines() {
+		let offsets: Vec<u32> = (0..65).collect();
+		let error = check_line_count(&offsets).err().unwrap();
+		assert_eq!(gpio_error_of(&error), &GpioError::TooManyLines { requested: 65, max: 64 });
+
+		assert!(check_line_count(&offsets[..64]).is_ok());
+	}
+
+	fn raw_line_info(flags: LineFlags, name: &str, consumer: &str) -> gpio_ioctl::GpioLineInfo {
+		let mut raw = gpio_ioctl::GpioLineInfo { flags: flags.bits(), ..Default::default() };
+		raw.name[..name.len()].copy_from_slice(name.as_bytes());
+		raw.consumer[..consumer.len()].copy_from_slice(consumer.as_bytes());
+		raw
+	}
+
+	#[test]
+	fn line_infos_differing_in_one_field_are_not_equal() {
+		let info = GpioLineInfo::from_raw(&raw_line_info(LineFlags::USED, "GPIO17", "app"));
+
+		assert_eq!(info, GpioLineInfo::from_raw(&raw_line_info(LineFlags::USED, "GPIO17", "app")));
+		assert_ne!(info, GpioLineInfo::from_raw(&raw_line_info(LineFlags::USED | LineFlags::OUTPUT, "GPIO17", "app")));
+		assert_ne!(info, GpioLineInfo::from_raw(&raw_line_info(LineFlags::USED, "GPIO18", "app")));
+		assert_ne!(info, GpioLineInfo::from_raw(&raw_line_info(LineFlags::USED, "GPIO17", "other")));
+	}
+
+	#[test]
+	fn find_by_stable_key_ignores_keys_without_a_line() {
+		// The fake chip fails every ioctl, so these keys must be rejected before searching the lines
+		let chip = fake_chip(PathBuf::from("/sys/bus/gpio/devices/gpiochip0"));
+
+		assert_eq!(chip.find_by_stable_key("fake:").unwrap(), None);
+		assert_eq!(chip.find_by_stable_key("fake").unwrap(), None);
+		assert_eq!(chip.find_by_stable_key("other:5").unwrap(), None);
+		assert!(chip.find_by_stable_key("fake:5").is_err());
+	}
+
+	fn fake_line_value(offset: Vec<u32>) -> GpioLineValue {
+		GpioLineValue {
+			parent_chip_name: String::from("gpiochip0"),
+			consumer: String::from("test"),
+			direction: LineDirection::Output,
+			output_mode: OutputMode::None,
+			active_low: false,
+			readable: true,
+			output_values: Arc::new(Mutex::new(vec![0; offset.len()])),
+			offset,
+			safe_state: None,
+			fd: File::open("/dev/null").unwrap(),
+		}
+	}
+
+	#[test]
+	fn bank_rejects_a_wrong_number_of_values_before_writing() {
+		let bank = GpioLineBank { lines: vec![fake_line_value(vec![0, 1]), fake_line_value(vec![2])] };
+
+		for values in [&[1, 0][..], &[1, 0, 1, 0][..]] {
+			let error = bank.set_line_values(values).err().unwrap();
+			assert_eq!(error.kind(), ErrorKind::InvalidInput);
+			assert!(error.get_ref().and_then(|inner| inner.downcast_ref::<PartialSetError>()).is_none());
+		}
+	}
+
+	#[test]
+	fn bank_reports_the_failing_request() {
+		// The fake requests fail every ioctl, so the first request fails before any line is written
+		let bank = GpioLineBank { lines: vec![fake_line_value(vec![0, 1]), fake_line_value(vec![2])] };
+
+		let error = bank.set_line_values(&[1, 0, 1]).err().unwrap();
+		let partial = error.get_ref().and_then(|inner| inner.downcast_ref::<PartialSetError>()).unwrap();
+		assert_eq!(partial.failed_request(), 0);
+		assert!(partial.written().is_empty());
+		let source = std::error::Error::source(partial).and_then(|source| source.downcast_ref::<io::Error>()).unwrap();
+		assert_eq!(source.raw_os_error(), Some(nix::libc::ENOTTY));
+	}
+
+	// Offsets, flags and default values of a recorded handle request
+	type HandleRequest = (Vec<u32>, u32, Vec<u8>);
+
+	// Records the kernel requests instead of calling the kernel. The line handles are the read ends of
+	// pipes whose write ends are kept, so that writing to them tells whether a handle was closed. The handle
+	// request with the index *busy_request*, if any, fails with EBUSY as if its lines were held elsewhere.
+	#[derive(Default)]
+	struct MockIoctl {
+		handle_requests: Mutex<Vec<HandleRequest>>,
+		handle_writers: Mutex<Vec<File>>,
+		busy_request: Option<usize>,
+		event_requests: Mutex<Vec<(u32, u32, u32)>>,
+	}
+
+	impl LineRequestIoctl for MockIoctl {
+		fn get_line_handle(&self, request: &mut gpio_ioctl::GpioHandleRequest) -> io::Result<()> {
+			let lines = request.lines as usize;
+			let mut handle_requests = self.handle_requests.lock().unwrap();
+			if self.busy_request == Some(handle_requests.len()) {
+				return Err(io::Error::from_raw_os_error(nix::libc::EBUSY));
+			}
+			handle_requests.push((request.line_offsets[..lines].to_vec(), request.flags, request.default_values[..lines].to_vec()));
+
+			let (reader, writer) = pipe_files();
+			self.handle_writers.lock().unwrap().push(writer);
+			request.fd = reader.into_raw_fd();
+			Ok(())
+		}
+
+		fn get_line_event(&self, request: &mut gpio_ioctl::GpioEventRequest) -> io::Result<()> {
+			self.event_requests.lock().unwrap().push((request.lineoffset, request.handleflags, request.eventflags));
+			request.fd = File::open("/dev/null")?.into_raw_fd();
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn output_options_map_to_kernel_flags() {
+		let chip = fake_chip(PathBuf::from("/sys/bus/gpio/devices/gpiochip0"));
+		let ioctl = MockIoctl::default();
+
+		let line = chip.request_output_with(&ioctl, &[2, 5], OutputMode::OpenDrain, true, &[1], "test").unwrap();
+		assert!(!line.readable);
+		chip.request_output_with(&ioctl, &[3], OutputMode::OpenSource, false, &[], "test").unwrap();
+		chip.request_output_with(&ioctl, &[4], OutputMode::None, false, &[1], "test").unwrap();
+
+		assert_eq!(*ioctl.handle_requests.lock().unwrap(), vec![
+			(vec![2, 5], GPIOHANDLE_REQUEST_OUTPUT | GPIOHANDLE_REQUEST_OPEN_DRAIN | GPIOHANDLE_REQUEST_ACTIVE_LOW, vec![1, 0]),
+			(vec![3], GPIOHANDLE_REQUEST_OUTPUT | GPIOHANDLE_REQUEST_OPEN_SOURCE, vec![0]),
+			(vec![4], GPIOHANDLE_REQUEST_OUTPUT, vec![1]),
+		]);
+	}
+
+	#[test]
+	fn input_options_map_to_kernel_flags() {
+		let chip = fake_chip(PathBuf::from("/sys/bus/gpio/devices/gpiochip0"));
+		let ioctl = MockIoctl::default();
+
+		chip.request_input_with(&ioctl, &[1], true, LineBias::PullUp, "test").unwrap();
+		chip.request_input_with(&ioctl, &[1], false, LineBias::PullDown, "test").unwrap();
+		chip.request_input_with(&ioctl, &[1], false, LineBias::Disabled, "test").unwrap();
+		chip.request_input_with(&ioctl, &[1], false, LineBias::None, "test").unwrap();
+
+		let flags: Vec<u32> = ioctl.handle_requests.lock().unwrap().iter().map(|request| request.1).collect();
+		assert_eq!(flags, vec![
+			GPIOHANDLE_REQUEST_INPUT | GPIOHANDLE_REQUEST_BIAS_PULL_UP | GPIOHANDLE_REQUEST_ACTIVE_LOW,
+			GPIOHANDLE_REQUEST_INPUT | GPIOHANDLE_REQUEST_BIAS_PULL_DOWN,
+			GPIOHANDLE_REQUEST_INPUT | GPIOHANDLE_REQUEST_BIAS_DISABLE,
+			GPIOHANDLE_REQUEST_INPUT,
+		]);
+	}
+
+	#[test]
+	fn edge_detection_maps_to_kernel_event_flags() {
+		let chip = fake_chip(PathBuf::from("/sys/bus/gpio/devices/gpiochip0"));
+		let ioctl = MockIoctl::default();
+
+		let events = chip.request_events_with(&ioctl, &[6, 7], EdgeDetection::RisingEdge, true, LineBias::PullDown, "test").unwrap();
+		assert_eq!(events.offset, vec![6, 7]);
+		chip.request_events_with(&ioctl, &[6], EdgeDetection::FallingEdge, false, LineBias::None, "test").unwrap();
+		chip.request_events_with(&ioctl, &[6], EdgeDetection::BothEdges, false, LineBias::None, "test").unwrap();
+
+		assert_eq!(*ioctl.event_requests.lock().unwrap(), vec![
+			(6, GPIOHANDLE_REQUEST_INPUT | GPIOHANDLE_REQUEST_BIAS_PULL_DOWN | GPIOHANDLE_REQUEST_ACTIVE_LOW, GPIOEVENT_REQUEST_RISING_EDGE),
+			(7, GPIOHANDLE_REQUEST_INPUT | GPIOHANDLE_REQUEST_BIAS_PULL_DOWN | GPIOHANDLE_REQUEST_ACTIVE_LOW, GPIOEVENT_REQUEST_RISING_EDGE),
+			(6, GPIOHANDLE_REQUEST_INPUT, GPIOEVENT_REQUEST_FALLING_EDGE),
+			(6, GPIOHANDLE_REQUEST_INPUT, GPIOEVENT_REQUEST_BOTH_EDGES),
+		]);
+	}
+
+	// Build an event request over pipes, one per line, returning the write ends to feed it records
+	fn pipe_line_event(offset: Vec<u32>) -> (GpioLineEvent, Vec<File>) {
+		let (fd, writers) = offset.iter().map(|_| pipe_files()).unzip();
+		let line_event = GpioLineEvent {
+			parent_chip_name: String::from("gpiochip0"),
+			consumer: String::from("test"),
+			offset,
+			fd,
+			events_read: 0,
+			nonblocking: false,
+		};
+		(line_event, writers)
+	}
+
+	fn write_event_record(writer: &mut File, timestamp: u64, id: u32) {
+		let mut record = [0u8; gpio_ioctl::GPIO_EVENT_DATA_SIZE];
+		record[0..8].copy_from_slice(&timestamp.to_ne_bytes());
+		record[8..12].copy_from_slice(&id.to_ne_bytes());
+		writer.write_all(&record).unwrap();
+	}
+
+	#[test]
+	fn read_event_reports_the_end_of_file_after_a_hang_up() {
+		let (mut line_event, mut writers) = pipe_line_event(vec![4]);
+
+		write_event_record(&mut writers[0], 1_000, GPIOEVENT_EVENT_FALLING_EDGE);
+		writers.clear();
+
+		assert_eq!(line_event.read_event().unwrap(), event(4, LineEdge::Falling, 1_000));
+		assert_eq!(line_event.read_event().err().unwrap().kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(line_event.drain_events().err().unwrap().kind(), ErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn reopen_repeats_how_the_chip_was_opened() {
+		let _lock = ENV_LOCK.lock().unwrap();
+		env::set_var("GPIOD_SYSFS_ROOT", fixture_dir("reopen-sysfs"));
+
+		// /dev/null fails the sysfs check of the empty fixture, while without the check it fails later in the ioctl
+		let unchecked = GpioChip { path: Some(PathBuf::from("/dev/null")), ..fake_chip(PathBuf::new()) };
+		assert_eq!(context_of(&unchecked.reopen().err().unwrap()), "Reading GPIO chip info");
+
+		let checked = GpioChip { path: Some(PathBuf::from("/dev/null")), sysfs_checked: true, ..fake_chip(PathBuf::new()) };
+		assert_eq!(context_of(&checked.reopen().err().unwrap()), "Checking GPIO chip device against sysfs");
+
+		assert_eq!(fake_chip(PathBuf::new()).reopen().err().unwrap().kind(), ErrorKind::NotFound);
+
+		env::remove_var("GPIOD_SYSFS_ROOT");
+	}
+
+	#[test]
+	fn nonblocking_reads_without_a_pending_event() {
+		let (mut line_event, mut writers) = pipe_line_event(vec![2, 3]);
+		line_event.set_nonblocking(true).unwrap();
+
+		assert_eq!(line_event.read_event().err().unwrap().kind(), ErrorKind::WouldBlock);
+		assert!(!line_event.has_pending_event().unwrap());
+		assert!(line_event.drain_events().unwrap().is_empty());
+
+		write_event_record(&mut writers[1], 10, GPIOEVENT_EVENT_RISING_EDGE);
+		write_event_record(&mut writers[0], 20, GPIOEVENT_EVENT_FALLING_EDGE);
+		assert!(line_event.has_pending_event().unwrap());
+		assert!(line_event.has_pending_event().unwrap());
+
+		let mut events = line_event.drain_events().unwrap();
+		events.sort_by(GpioEvent::cmp_order);
+		assert_eq!(events, vec![event(3, LineEdge::Rising, 10), event(2, LineEdge::Falling, 20)]);
+		assert!(!line_event.has_pending_event().unwrap());
+		assert_eq!(line_event.read_event().err().unwrap().kind(), ErrorKind::WouldBlock);
+	}
+
+	#[cfg(feature = "tokio")]
+	fn block_on<F: std::future::Future>(future: F) -> F::Output {
+		tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(future)
+	}
+
+	#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+	fn block_on<F: std::future::Future>(future: F) -> F::Output {
+		async_std::task::block_on(future)
+	}
+
+	#[cfg(any(feature = "tokio", feature = "async-std"))]
+	#[test]
+	fn read_event_async_timeout_returns_the_event_or_none() {
+		let (mut line_event, mut writers) = pipe_line_event(vec![1]);
+
+		let timeout = block_on(line_event.read_event_async_timeout(Duration::from_millis(20))).unwrap();
+		assert_eq!(timeout, None);
+
+		write_event_record(&mut writers[0], 42, GPIOEVENT_EVENT_RISING_EDGE);
+		let arrived = block_on(line_event.read_event_async_timeout(Duration::from_secs(5))).unwrap();
+		assert_eq!(arrived, Some(event(1, LineEdge::Rising, 42)));
+
+		let writing = thread::spawn(move || {
+			thread::sleep(Duration::from_millis(20));
+			write_event_record(&mut writers[0], 43, GPIOEVENT_EVENT_FALLING_EDGE);
+		});
+		let waited = block_on(line_event.read_event_async_timeout(Duration::from_secs(5))).unwrap();
+		writing.join().unwrap();
+		assert_eq!(waited, Some(event(1, LineEdge::Falling, 43)));
+	}
+
+	#[test]
+	fn count_edges_counts_every_line_and_leaves_later_events_queued() {
+		let (mut line_event, mut writers) = pipe_line_event(vec![1, 2, 3]);
+
+		write_event_record(&mut writers[0], 10, GPIOEVENT_EVENT_RISING_EDGE);
+		write_event_record(&mut writers[0], 20, GPIOEVENT_EVENT_FALLING_EDGE);
+		write_event_record(&mut writers[2], 30, GPIOEVENT_EVENT_RISING_EDGE);
+
+		let counts = line_event.count_edges(Duration::from_millis(20)).unwrap();
+		assert_eq!(counts, HashMap::from([(1, 2), (2, 0), (3, 1)]));
+
+		let mut late_writer = writers.remove(1);
+		let writing = thread::spawn(move || {
+			thread::sleep(Duration::from_millis(200));
+			write_event_record(&mut late_writer, 40, GPIOEVENT_EVENT_RISING_EDGE);
+		});
+		let counts = line_event.count_edges(Duration::from_millis(20)).unwrap();
+		writing.join().unwrap();
+		assert_eq!(counts, HashMap::from([(1, 0), (2, 0), (3, 0)]));
+
+		assert!(line_event.has_pending_event().unwrap());
+		assert_eq!(line_event.read_event().unwrap(), event(2, LineEdge::Rising, 40));
+	}
+
+	#[test]
+	fn read_event_deadline_in_the_past_does_not_poll() {
+		let (mut line_event, mut writers) = pipe_line_event(vec![5]);
+		write_event_record(&mut writers[0], 10, GPIOEVENT_EVENT_RISING_EDGE);
+
+		// The pending event would be returned if the past deadline still polled the file
+		let past = Instant::now().checked_sub(Duration::from_millis(10)).unwrap_or_else(Instant::now);
+		assert_eq!(line_event.read_event_deadline(past).unwrap(), None);
+		assert!(line_event.has_pending_event().unwrap());
+
+		let deadline = Instant::now() + Duration::from_secs(5);
+		assert_eq!(line_event.read_event_deadline(deadline).unwrap(), Some(event(5, LineEdge::Rising, 10)));
+		assert_eq!(line_event.read_event_deadline(Instant::now() + Duration::from_millis(20)).unwrap(), None);
+	}
+
+	#[test]
+	fn capture_writes_a_header_and_one_row_per_event() {
+		for (format, expected) in [
+			(CaptureFormat::Csv, "timestamp_ns,line,edge\n10,4,rising\n30,4,unknown-7\n20,6,falling\n"),
+			(CaptureFormat::Tsv, "timestamp_ns\tline\tedge\n10\t4\trising\n30\t4\tunknown-7\n20\t6\tfalling\n"),
+		] {
+			let (mut line_event, mut writers) = pipe_line_event(vec![4, 6]);
+			write_event_record(&mut writers[0], 10, GPIOEVENT_EVENT_RISING_EDGE);
+			write_event_record(&mut writers[1], 20, GPIOEVENT_EVENT_FALLING_EDGE);
+			write_event_record(&mut writers[0], 30, 7);
+
+			// The events are already queued, so each round of the capture reads one of them, taking the
+			// lines in order as poll reports the first readable one
+			let rounds = std::cell::Cell::new(0);
+			let mut output = Vec::new();
+			let written = line_event.capture_to(&mut output, format, || {
+				rounds.set(rounds.get() + 1);
+				rounds.get() > 3
+			}).unwrap();
+
+			assert_eq!(written, 3);
+			assert_eq!(String::from_utf8(output).unwrap(), expected);
+		}
+	}
+
+	#[test]
+	fn empty_requests_are_rejected_before_the_kernel() {
+		let chip = fake_chip(PathBuf::new());
+
+		let error = chip.request_line_values_input(&[], false, "test").err().unwrap();
+		assert_eq!(gpio_error_of(&error), &GpioError::NoLines);
+	}
+
+	#[test]
+	fn offsets_beyond_the_chip_are_rejected_before_the_kernel() {
+		let chip = fake_chip(PathBuf::new());
+		let ioctl = MockIoctl::default();
+
+		let error = chip.request_input_with(&ioctl, &[8], false, LineBias::None, "test").err().unwrap();
+		assert_eq!(gpio_error_of(&error), &GpioError::InvalidOffset { offset: 8, num_lines: 8 });
+		assert!(ioctl.handle_requests.lock().unwrap().is_empty());
+
+		chip.request_input_with(&ioctl, &[7], false, LineBias::None, "test").unwrap();
+	}
+
+	#[test]
+	fn retry_on_interrupt_repeats_only_interrupted_calls() {
+		let calls = std::cell::Cell::new(0);
+		let result = retry_on_interrupt(|| {
+			calls.set(calls.get() + 1);
+			if calls.get() == 1 { Err(nix::Error::Sys(nix::errno::Errno::EINTR)) } else { Ok(5) }
+		});
+		assert_eq!(result.unwrap(), 5);
+		assert_eq!(calls.get(), 2);
+
+		calls.set(0);
+		let result = retry_on_interrupt(|| {
+			calls.set(calls.get() + 1);
+			Err(nix::Error::Sys(nix::errno::Errno::EBUSY))
+		});
+		assert_eq!(result.err().unwrap().raw_os_error(), Some(nix::libc::EBUSY));
+		assert_eq!(calls.get(), 1);
+	}
+
+	#[test]
+	fn batch_releases_the_earlier_handles_when_a_request_is_busy() {
+		let chip = fake_chip(PathBuf::new());
+		let ioctl = MockIoctl { busy_request: Some(2), ..Default::default() };
+		let input = |line: u32| LineRequest::Input { line_offset: vec![line], active_low: false, label: String::from("test") };
+
+		let requests = [
+			input(0),
+			LineRequest::Output { line_offset: vec![1, 2], output_mode: OutputMode::None, active_low: false, label: String::from("test") },
+			input(3),
+			input(4),
+		];
+		let error = chip.request_batch_with(&ioctl, &requests).err().unwrap();
+		assert_eq!(ContextError::raw_os_error(&error), Some(nix::libc::EBUSY));
+		assert_eq!(ioctl.handle_requests.lock().unwrap().len(), 2);
+
+		// Both handles handed out before the busy request were closed again
+		for writer in ioctl.handle_writers.lock().unwrap().iter_mut() {
+			assert_eq!(writer.write(&[0]).err().unwrap().kind(), ErrorKind::BrokenPipe);
+		}
+		assert_eq!(ioctl.handle_writers.lock().unwrap().len(), 2);
+
+		let ioctl = MockIoctl::default();
+		assert_eq!(chip.request_batch_with(&ioctl, &requests).unwrap().len(), 4);
+	}
+
+	#[test]
+	fn line_mask_covers_the_requested_lines() {
+		assert_eq!(fake_line_value(vec![3, 5, 9]).line_mask(), 0b111);
+		assert_eq!(fake_line_value(vec![]).line_mask(), 0);
+		assert_eq!(fake_line_value((0..64).collect()).line_mask(), u64::MAX);
+	}
+
+	#[test]
+	fn set_bits_masked_rejects_masks_beyond_the_request() {
+		let line = fake_line_value(vec![3, 5, 9]);
+
+		let error = line.set_bits_masked(0, 0b1000).err().unwrap();
+		assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+		// A valid mask reaches the kernel, which the fake request rejects without changing the written values
+		assert!(line.set_bits_masked(0b101, 0b111).err().unwrap().kind() != ErrorKind::InvalidInput);
+		assert_eq!(*line.output_values.lock().unwrap(), vec![0, 0, 0]);
+	}
+}